@@ -3,8 +3,6 @@
 //!
 //! Talks to the the tide subscription example in `async-graphql`
 
-use std::future::IntoFuture;
-
 use graphql_ws_client::{ws_stream_wasm::Connection, Client};
 
 mod schema {
@@ -58,7 +56,10 @@ async fn main() {
     let connection = Connection::new(ws_conn).await;
 
     let (client, actor) = Client::build(connection).await.unwrap();
-    wasm_bindgen_futures::spawn_local(actor.into_future());
+    wasm_bindgen_futures::spawn_local(async move {
+        let reason = actor.await;
+        info!("Connection shut down: {:?}", reason);
+    });
 
     let mut stream = client.subscribe(build_query()).await.unwrap();
     info!("Running subscription");
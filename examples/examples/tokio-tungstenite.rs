@@ -1,5 +1,5 @@
 //! An example of using subscriptions with `graphql-ws-client` and
-//! `async-tungstenite`
+//! `tokio-tungstenite`
 //!
 //! Talks to the the tide subscription example in `async-graphql`
 
@@ -39,20 +39,15 @@ struct BooksChangedSubscription {
 #[tokio::main]
 async fn main() {
     use futures::StreamExt;
-    use graphql_ws_client::Client;
-    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, http::HeaderValue};
+    use graphql_ws_client::{tokio_tungstenite_0_27::ClientBuilderExt, ClientBuilder};
 
-    let mut request = "ws://localhost:8000/graphql".into_client_request().unwrap();
-    request.headers_mut().insert(
-        "Sec-WebSocket-Protocol",
-        HeaderValue::from_str("graphql-transport-ws").unwrap(),
-    );
-
-    let (connection, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+    let builder = ClientBuilder::connect("ws://localhost:8000/graphql")
+        .await
+        .unwrap();
 
     println!("Connected");
 
-    let (client, actor) = Client::build(connection).await.unwrap();
+    let (client, actor) = builder.await.unwrap();
     tokio::spawn(actor.into_future());
 
     let mut stream = client.subscribe(build_query()).await.unwrap();
@@ -1,21 +1,38 @@
 use std::{
     collections::{HashMap, hash_map::Entry},
     future::IntoFuture,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
 };
 
 use futures_lite::{FutureExt, StreamExt, future, stream};
-use serde_json::{Value, json};
+use serde_json::Value;
+use web_time::Instant;
 
 use crate::{
-    Error, SubscriptionId,
+    CloseCode, Error, SubscriptionId,
     logging::{trace, warning},
-    protocol::Event,
+    protocol::{Event, Protocol},
 };
 
 use super::{
     ConnectionCommand,
+    completion::{CompletionReason, CompletionSender},
     connection::{Message, ObjectSafeConnection},
+    events::{ConnectionEvent, EventSender},
+    handshake::{InitPayload, handshake},
     keepalive::KeepAliveSettings,
+    latency::LatencyTracker,
+    malformed::MalformedMessages,
+    outcome::{OutcomeSender, outcome},
+    overflow::OverflowPolicy,
+    persisted::{is_persisted_query_not_found, is_persisted_query_not_found_response},
+    ping::PingPayloads,
+    reconnect::ReconnectSettings,
+    shutdown::{ActorHandle, ShutdownReason},
 };
 
 #[must_use]
@@ -27,133 +44,606 @@ use super::{
 pub struct ConnectionActor {
     client: async_channel::Receiver<ConnectionCommand>,
     connection: Box<dyn ObjectSafeConnection>,
-    dropped_ids: async_channel::Receiver<SubscriptionId>,
-    operations: HashMap<SubscriptionId, async_channel::Sender<Value>>,
+    dropped: async_channel::Receiver<ConnectionCommand>,
+    operations: HashMap<SubscriptionId, ActiveOperation>,
     keep_alive: KeepAliveSettings,
     keep_alive_actor: stream::Boxed<ConnectionCommand>,
+    reconnect: ReconnectSettings,
+    payload: InitPayload,
+    init_timeout: Option<Duration>,
+    protocol: Protocol,
+    pings: PingPayloads,
+    malformed: MalformedMessages,
+    events: EventSender,
+    latency: LatencyTracker,
+
+    /// The number of running operations, shared with the clients
+    operation_count: Arc<AtomicUsize>,
+
+    /// When the most recent unanswered ping was sent
+    ping_sent_at: Option<Instant>,
+
+    /// Waiting on the `complete` messages for stopped operations to be sent
+    stopping: Vec<async_channel::Sender<()>>,
+
+    shutdown_reason: Option<OutcomeSender<ShutdownReason>>,
+    handle: ActorHandle,
+}
+
+struct ActiveOperation {
+    /// The full subscribe request, kept so the operation can be restarted
+    /// after a reconnect.
+    request: String,
+    /// The request to retry with if the server doesn't recognise a persisted query
+    fallback: Option<String>,
+    /// Set when the server has said in a `next` that it doesn't recognise the
+    /// persisted query, so the operation is retried once the server completes it
+    retry_on_complete: bool,
+    sender: async_channel::Sender<Result<Value, Error>>,
+    overflow_policy: OverflowPolicy,
+    completion: CompletionSender,
+    /// Closes once the operation's stream has been read to the end
+    drained: async_channel::Receiver<()>,
+}
+
+/// The settings from a `ClientBuilder` that the actor makes use of
+pub(super) struct ActorSettings {
+    pub(super) keep_alive: KeepAliveSettings,
+    pub(super) reconnect: ReconnectSettings,
+    pub(super) payload: InitPayload,
+    pub(super) init_timeout: Option<Duration>,
+    pub(super) protocol: Protocol,
+    pub(super) pings: PingPayloads,
+    pub(super) malformed: MalformedMessages,
 }
 
 impl ConnectionActor {
     pub(super) fn new(
         connection: Box<dyn ObjectSafeConnection>,
         client: async_channel::Receiver<ConnectionCommand>,
-        dropped_ids: async_channel::Receiver<SubscriptionId>,
-        keep_alive: KeepAliveSettings,
+        dropped: async_channel::Receiver<ConnectionCommand>,
+        settings: ActorSettings,
+        events: EventSender,
+        latency: LatencyTracker,
+        operation_count: Arc<AtomicUsize>,
     ) -> Self {
+        let ActorSettings {
+            keep_alive,
+            reconnect,
+            payload,
+            init_timeout,
+            protocol,
+            pings,
+            malformed,
+        } = settings;
+
+        let (shutdown_reason, reason) = outcome();
+
         ConnectionActor {
             client,
             connection,
-            dropped_ids,
+            dropped,
             operations: HashMap::new(),
             keep_alive_actor: Box::pin(keep_alive.run()),
             keep_alive,
+            reconnect,
+            payload,
+            init_timeout,
+            protocol,
+            pings,
+            malformed,
+            events,
+            latency,
+            operation_count,
+            ping_sent_at: None,
+            stopping: Vec::new(),
+            shutdown_reason: Some(shutdown_reason),
+            handle: ActorHandle { reason },
         }
     }
 
-    async fn run(mut self) {
+    /// Returns a handle that can be used to find out why this actor stopped
+    pub fn handle(&self) -> ActorHandle {
+        self.handle.clone()
+    }
+
+    async fn run(mut self) -> ShutdownReason {
+        let reason = loop {
+            let reason = self.run_connection().await;
+            if !reason.is_transport_failure() || !self.reconnect().await {
+                break reason;
+            }
+        };
+
+        let completion = CompletionReason::from(&reason);
+        for (_, operation) in self.operations.drain() {
+            operation.completion.send(completion.clone());
+        }
+        self.operations_changed();
+
+        if let Some(shutdown_reason) = self.shutdown_reason.take() {
+            shutdown_reason.send(reason.clone());
+        }
+
+        reason
+    }
+
+    /// Services the current connection until it either drops or is closed.
+    async fn run_connection(&mut self) -> ShutdownReason {
         while let Some(next) = self.next().await {
             let response = match next {
+                Next::Command(ConnectionCommand::Close(code, reason)) => {
+                    self.close(Message::Close {
+                        code: Some(code),
+                        reason: Some(reason.clone()),
+                    })
+                    .await;
+                    return ShutdownReason::ClientClosed {
+                        code,
+                        reason: Some(reason),
+                    };
+                }
+                Next::Command(ConnectionCommand::Shutdown { timeout, done }) => {
+                    let reason = self.shutdown(timeout).await;
+                    drop(done);
+                    return reason;
+                }
                 Next::Command(cmd) => self.handle_command(cmd),
-                Next::Message(message) => self.handle_message(message).await,
+                Next::Message(Message::Close { code, reason }) => {
+                    trace!("Connection closed by server");
+                    self.events.send(ConnectionEvent::ServerClosed {
+                        code,
+                        reason: reason.clone(),
+                    });
+                    let message = Message::Close {
+                        code,
+                        reason: reason.clone(),
+                    };
+                    if let Ok(Some(response)) = self.handle_message(message).await {
+                        self.connection.send(response).await.ok();
+                    }
+                    return ShutdownReason::ServerClosed { code, reason };
+                }
+                Next::Message(message) => match self.handle_message(message).await {
+                    Ok(response) => response,
+                    Err(reason) => {
+                        self.close(Message::Close {
+                            code: Some(CloseCode::Normal),
+                            reason: None,
+                        })
+                        .await;
+                        return reason;
+                    }
+                },
+                Next::ConnectionLost => return ShutdownReason::ConnectionLost,
+                Next::KeepAliveTimeout => {
+                    self.events.send(ConnectionEvent::KeepAliveFailed);
+                    self.connection
+                        .send(self.keep_alive.report_timeout())
+                        .await
+                        .ok();
+                    return ShutdownReason::KeepAliveTimeout;
+                }
             };
 
             let Some(response) = response else { continue };
 
-            if matches!(response, Message::Close { .. }) {
-                self.connection.send(response).await.ok();
-                return;
+            // Close messages at this point are due to the server breaking the protocol
+            if let Message::Close { code, reason } = &response {
+                let reason = reason.clone().unwrap_or_default();
+                let reason = match code {
+                    Some(CloseCode::DecodeError) => ShutdownReason::DecodeError(reason),
+                    _ => ShutdownReason::ProtocolError(reason),
+                };
+                self.close(response).await;
+                return reason;
             }
 
-            if self.connection.send(response).await.is_err() {
-                return;
+            let sent = self.connection.send(response).await;
+
+            // Any stopped operations have now been completed
+            self.stopping.clear();
+
+            if let Err(error) = sent {
+                return ShutdownReason::SendFailed(error.to_string());
+            }
+        }
+
+        self.close(Message::Close {
+            code: Some(CloseCode::ClientsDropped),
+            reason: None,
+        })
+        .await;
+
+        ShutdownReason::ClientsDropped
+    }
+
+    /// Completes every running operation, waits for their streams to be
+    /// drained and then closes the connection.
+    ///
+    /// The connection is still serviced while waiting, so pings are answered,
+    /// but any new subscriptions are rejected.  A `Client::close` while waiting
+    /// closes the connection straight away.
+    async fn shutdown(&mut self, timeout: Duration) -> ShutdownReason {
+        enum Drain {
+            Finished,
+            Next(Option<Next>),
+        }
+
+        let normal = ShutdownReason::ClientClosed {
+            code: CloseCode::Normal,
+            reason: None,
+        };
+
+        // Any other shutdowns resolve along with this one, once these are dropped
+        let mut waiting = Vec::new();
+
+        let mut sent = Ok(());
+        let mut drained = Vec::with_capacity(self.operations.len());
+        for (id, operation) in self.operations.drain() {
+            if sent.is_ok() {
+                sent = self
+                    .connection
+                    .send(self.protocol.complete(id.to_string()))
+                    .await;
+            }
+            operation.completion.send(CompletionReason::Stopped);
+
+            // Dropping the sender ends the stream once the responses it has
+            // already buffered have been read
+            drained.push(operation.drained);
+        }
+        self.operations_changed();
+
+        if sent.is_err() {
+            return normal;
+        }
+
+        let mut drained = std::pin::pin!(async move {
+            for operation in drained {
+                operation.recv().await.ok();
+            }
+        });
+        let mut deadline = futures_timer::Delay::new(timeout);
+
+        loop {
+            let finished = async {
+                drained.as_mut().or(&mut deadline).await;
+                Drain::Finished
+            };
+            let next = async { Drain::Next(self.next().await) };
+
+            let response = match finished.or(next).await {
+                // Either every stream has been read, we've run out of time or
+                // every client has gone away
+                Drain::Finished | Drain::Next(None) => break,
+                Drain::Next(Some(Next::Command(ConnectionCommand::Subscribe {
+                    started, ..
+                }))) => {
+                    started.try_send(Err(Error::ShuttingDown)).ok();
+                    None
+                }
+                Drain::Next(Some(Next::Command(ConnectionCommand::Close(code, reason)))) => {
+                    self.close(Message::Close {
+                        code: Some(code),
+                        reason: Some(reason.clone()),
+                    })
+                    .await;
+                    return ShutdownReason::ClientClosed {
+                        code,
+                        reason: Some(reason),
+                    };
+                }
+                Drain::Next(Some(Next::Command(ConnectionCommand::Shutdown { done, .. }))) => {
+                    waiting.push(done);
+                    None
+                }
+                Drain::Next(Some(Next::Command(cmd))) => self.handle_command(cmd),
+                Drain::Next(Some(
+                    Next::Message(Message::Close { .. })
+                    | Next::ConnectionLost
+                    | Next::KeepAliveTimeout,
+                )) => return normal,
+                Drain::Next(Some(Next::Message(message))) => {
+                    match self.handle_message(message).await {
+                        Ok(response) => response,
+                        // The connection is closed below anyway
+                        Err(_) => break,
+                    }
+                }
+            };
+
+            let Some(response) = response else { continue };
+            if let Message::Close { .. } = response {
+                self.close(response).await;
+                return normal;
+            }
+
+            let sent = self.connection.send(response).await;
+            self.stopping.clear();
+            if sent.is_err() {
+                return normal;
+            }
+        }
+
+        self.close(Message::Close {
+            code: Some(CloseCode::Normal),
+            reason: None,
+        })
+        .await;
+
+        normal
+    }
+
+    /// Closes the connection from the client side
+    async fn close(&mut self, close: Message) {
+        if let Some(terminate) = self.protocol.terminate() {
+            self.connection.send(terminate).await.ok();
+        }
+        self.connection.send(close).await.ok();
+    }
+
+    /// Attempts to re-establish a dropped connection, returning false if
+    /// reconnection is disabled or every attempt failed.
+    async fn reconnect(&mut self) -> bool {
+        if !self.reconnect.enabled() {
+            return false;
+        }
+
+        for attempt in 1..=self.reconnect.attempts {
+            futures_timer::Delay::new(self.reconnect.delay).await;
+
+            trace!("Reconnecting, attempt {attempt}");
+            self.events.send(ConnectionEvent::Reconnecting { attempt });
+
+            match self.try_reconnect().await {
+                Ok(()) => return true,
+                #[allow(unused_variables)] // error is only used when logging is enabled
+                Err(error) => {
+                    warning!("reconnection attempt {attempt} failed: {error}");
+                }
             }
         }
 
-        self.connection
-            .send(Message::Close {
-                code: Some(100),
-                reason: None,
-            })
-            .await
-            .ok();
+        false
+    }
+
+    async fn try_reconnect(&mut self) -> Result<(), Error> {
+        let mut connection = self.reconnect.connect().await?;
+
+        let ack_payload = handshake(
+            &mut *connection,
+            self.protocol,
+            &self.payload,
+            self.init_timeout,
+            &self.pings,
+        )
+        .await?;
+
+        // Subscriptions that were dropped while we were disconnected don't need restarting
+        let dropped = self
+            .operations
+            .iter()
+            .filter(|(_, operation)| operation.sender.is_closed())
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+        for id in dropped {
+            self.finish(id.as_str(), CompletionReason::Stopped);
+        }
+
+        for operation in self.operations.values() {
+            connection
+                .send(Message::Text(operation.request.clone()))
+                .await?;
+        }
+
+        self.connection = connection;
+        self.keep_alive_actor = Box::pin(self.keep_alive.run());
+        self.ping_sent_at = None;
+
+        self.events.send(ConnectionEvent::Connected {
+            payload: ack_payload,
+        });
+
+        Ok(())
     }
 
     fn handle_command(&mut self, cmd: ConnectionCommand) -> Option<Message> {
         match cmd {
             ConnectionCommand::Subscribe {
                 request,
+                fallback,
                 sender,
+                overflow_policy,
                 id,
+                started,
+                completion,
+                drained,
             } => {
-                assert!(self.operations.insert(id, sender).is_none());
+                let entry = match self.operations.entry(id) {
+                    Entry::Vacant(entry) => entry,
+                    Entry::Occupied(entry) => {
+                        warning!("Subscription id {} is already in use", entry.key());
+                        let id = entry.key().clone();
+                        started
+                            .try_send(Err(Error::SubscriberAlreadyExists(id)))
+                            .ok();
+                        return None;
+                    }
+                };
+
+                entry.insert(ActiveOperation {
+                    request: request.clone(),
+                    fallback,
+                    retry_on_complete: false,
+                    sender,
+                    overflow_policy,
+                    completion,
+                    drained,
+                });
+                self.operations_changed();
+                started.try_send(Ok(())).ok();
 
                 Some(Message::Text(request))
             }
-            ConnectionCommand::Cancel(id) => {
-                if self.operations.remove(&id).is_some() {
-                    return Some(Message::complete(id));
+            ConnectionCommand::Cancel { id, stopped } => {
+                if self.finish(id.as_str(), CompletionReason::Stopped) {
+                    self.stopping.extend(stopped);
+                    return Some(self.protocol.complete(id.to_string()));
                 }
                 None
             }
-            ConnectionCommand::Close(code, reason) => Some(Message::Close {
-                code: Some(code),
-                reason: Some(reason),
-            }),
-            ConnectionCommand::Ping => Some(Message::graphql_ping()),
+            // These end the connection, so are handled by run_connection & shutdown
+            ConnectionCommand::Close(..) | ConnectionCommand::Shutdown { .. } => None,
+            ConnectionCommand::Ping => {
+                let ping = self.protocol.ping(self.pings.ping())?;
+                // Any earlier ping has gone unanswered, so we time from this one
+                self.ping_sent_at = Some(Instant::now());
+                Some(ping)
+            }
         }
     }
 
-    async fn handle_message(&mut self, message: Message) -> Option<Message> {
-        let event = match extract_event(message) {
-            Ok(event) => event?,
-            Err(Error::Close(code, reason)) => {
-                return Some(Message::Close {
-                    code: Some(code),
-                    reason: Some(reason),
-                });
+    /// Restarts an operation with its full query, after the server didn't
+    /// recognise its persisted query
+    fn retry_with_full_query(&mut self, id: &str) -> Option<Message> {
+        let operation = self.operations.get_mut(id)?;
+        operation.request = operation.fallback.take()?;
+        operation.retry_on_complete = false;
+
+        trace!("Persisted query not found, retrying with the full query");
+        Some(Message::Text(operation.request.clone()))
+    }
+
+    /// Removes a running operation, returning false if it wasn't running
+    fn finish(&mut self, id: &str, reason: CompletionReason) -> bool {
+        let Some(operation) = self.operations.remove(id) else {
+            return false;
+        };
+        self.operations_changed();
+        operation.completion.send(reason);
+        true
+    }
+
+    fn operations_changed(&self) {
+        self.operation_count
+            .store(self.operations.len(), Ordering::Relaxed);
+    }
+
+    /// Handles a message from the server, returning the response to send.
+    ///
+    /// Returns `Err` if the server rejected the connection, in which case it
+    /// should be closed.
+    async fn handle_message(
+        &mut self,
+        message: Message,
+    ) -> Result<Option<Message>, ShutdownReason> {
+        let event = match message {
+            Message::Text(text) => {
+                trace!("Decoding message: {}", text);
+                match self.protocol.decode(&text) {
+                    Ok(event) => event,
+                    Err(error) => return Ok(self.malformed.handle(&text, &error)),
+                }
             }
-            Err(other) => {
-                return Some(Message::Close {
-                    code: Some(4857),
-                    reason: Some(format!("Error while decoding event: {other}")),
-                });
+            Message::Close { code, reason } => {
+                // 1005 means no code was sent, so it must not be sent on the wire
+                let code = code.filter(|code| *code != CloseCode::NoStatusReceived);
+                return Ok(Some(Message::Close {
+                    code: Some(code.unwrap_or(CloseCode::Normal)),
+                    reason: Some(reason.unwrap_or_default()),
+                }));
             }
+            Message::Ping | Message::Pong => return Ok(None),
         };
 
-        match event {
-            event @ (Event::Next { .. } | Event::Error { .. }) => {
-                let Some(id) = event.id().and_then(SubscriptionId::from_str) else {
-                    return Some(Message::close(Reason::UnknownSubscription));
-                };
+        if let Event::ConnectionError { payload } = event {
+            trace!("Connection rejected by server");
+            return Err(ShutdownReason::ServerRejected { payload });
+        }
 
-                let sender = self.operations.entry(id);
+        Ok(self.handle_event(event).await)
+    }
 
-                let Entry::Occupied(mut sender) = sender else {
-                    return None;
-                };
+    async fn handle_event(&mut self, event: Event) -> Option<Message> {
+        match &event {
+            Event::Error { id, payload } if is_persisted_query_not_found(payload) => {
+                if let Some(retry) = self.retry_with_full_query(id) {
+                    return Some(retry);
+                }
+            }
+            Event::Next { id, payload } if is_persisted_query_not_found_response(payload) => {
+                // The server will complete the operation after this result, so
+                // we wait for that before retrying with the same id
+                if let Some(operation) = self.operations.get_mut(id.as_str()) {
+                    if operation.fallback.is_some() {
+                        operation.retry_on_complete = true;
+                        return None;
+                    }
+                }
+            }
+            Event::Complete { id }
+                if self
+                    .operations
+                    .get(id.as_str())
+                    .is_some_and(|operation| operation.retry_on_complete) =>
+            {
+                return self.retry_with_full_query(id);
+            }
+            _ => {}
+        }
 
-                let payload = event.forwarding_payload().unwrap();
+        match event {
+            event @ (Event::Next { .. } | Event::Error { .. }) => {
+                // An error message ends the operation, so the server won't send a complete
+                let finished = matches!(event, Event::Error { .. });
+                let (id, payload) = event.forwarding_payload()?;
+
+                // Operations we don't know about have already been stopped, so are ignored
+                let ActiveOperation {
+                    sender,
+                    overflow_policy,
+                    ..
+                } = self.operations.get(id.as_str())?;
+
+                if let Err(reason) = overflow_policy.send(sender, payload).await {
+                    self.finish(&id, reason);
+                    return Some(self.protocol.complete(id));
+                }
 
-                if sender.get_mut().send(payload).await.is_err() {
-                    sender.remove();
-                    return Some(Message::complete(id));
+                if finished {
+                    trace!("Stream errored");
+                    self.finish(&id, CompletionReason::Errored);
                 }
 
                 None
             }
             Event::Complete { id } => {
-                let Some(id) = SubscriptionId::from_str(&id) else {
-                    return Some(Message::close(Reason::UnknownSubscription));
-                };
-
                 trace!("Stream complete");
 
-                self.operations.remove(&id);
+                self.finish(&id, CompletionReason::Completed);
                 None
             }
             Event::ConnectionAck { .. } => Some(Message::close(Reason::UnexpectedAck)),
-            Event::Ping { .. } => Some(Message::graphql_pong()),
-            Event::Pong { .. } => None,
+            Event::Ping { payload } => {
+                let pong = self.protocol.pong(self.pings.pong(payload.as_ref()));
+                self.events.send(ConnectionEvent::Ping { payload });
+                pong
+            }
+            Event::Pong { payload } => {
+                // Pongs that don't answer one of our pings have no round trip time
+                let round_trip_time = self.ping_sent_at.take().map(|sent_at| sent_at.elapsed());
+                if let Some(round_trip_time) = round_trip_time {
+                    self.latency.record(round_trip_time);
+                }
+                self.events.send(ConnectionEvent::Pong {
+                    payload,
+                    round_trip_time,
+                });
+                None
+            }
+            // Handled by handle_message
+            Event::ConnectionError { .. } => None,
         }
     }
 
@@ -165,13 +655,12 @@ impl ConnectionActor {
         }
 
         let dropped_id = async {
-            Select::Command(
-                self.dropped_ids
-                    .recv()
-                    .await
-                    .ok()
-                    .map(ConnectionCommand::Cancel),
-            )
+            match self.dropped.recv().await {
+                Ok(command) => Select::Command(Some(command)),
+                // Every client also holds a command sender, so leave it to the command
+                // channel to report when they're gone - it might still have commands queued.
+                Err(_) => future::pending().await,
+            }
         };
         let command = async { Select::Command(self.client.recv().await.ok()) };
         let message = async { Select::Message(self.connection.receive().await) };
@@ -185,85 +674,73 @@ impl ConnectionActor {
                 // All clients have disconnected
                 None
             }
-            Select::Message(message) => {
+            Select::Message(None) => Some(Next::ConnectionLost),
+            Select::Message(Some(message)) => {
                 self.keep_alive_actor = Box::pin(self.keep_alive.run());
-                Some(Next::Message(message?))
+                Some(Next::Message(message))
             }
-            Select::KeepAlive(None) => Some(self.keep_alive.report_timeout()),
+            Select::KeepAlive(None) => Some(Next::KeepAliveTimeout),
         }
     }
 }
 
+impl Drop for ConnectionActor {
+    fn drop(&mut self) {
+        self.events.send(ConnectionEvent::Shutdown);
+    }
+}
+
 enum Next {
     Command(ConnectionCommand),
     Message(Message),
+    ConnectionLost,
+    KeepAliveTimeout,
 }
 
 impl IntoFuture for ConnectionActor {
-    type Output = ();
+    type Output = ShutdownReason;
 
-    type IntoFuture = future::Boxed<()>;
+    type IntoFuture = future::Boxed<ShutdownReason>;
 
     fn into_future(self) -> Self::IntoFuture {
         Box::pin(self.run())
     }
 }
 
-fn extract_event(message: Message) -> Result<Option<Event>, Error> {
-    match message {
-        Message::Text(s) => {
-            trace!("Decoding message: {}", s);
-            Ok(Some(
-                serde_json::from_str(&s).map_err(|err| Error::Decode(err.to_string()))?,
-            ))
-        }
-        Message::Close { code, reason } => Err(Error::Close(
-            code.unwrap_or_default(),
-            reason.unwrap_or_default(),
-        )),
-        Message::Ping | Message::Pong => Ok(None),
-    }
-}
-
 enum Reason {
     UnexpectedAck,
-    UnknownSubscription,
 }
 
 impl Message {
     fn close(reason: Reason) -> Self {
         match reason {
             Reason::UnexpectedAck => Message::Close {
-                code: Some(4855),
+                code: Some(CloseCode::TooManyAcknowledges),
                 reason: Some("too many acknowledges".into()),
             },
-            Reason::UnknownSubscription => Message::Close {
-                code: Some(4856),
-                reason: Some("unknown subscription".into()),
-            },
         }
     }
 }
 
 impl Event {
-    fn forwarding_payload(self) -> Option<Value> {
+    fn forwarding_payload(self) -> Option<(String, Result<Value, Error>)> {
         match self {
-            Event::Next { payload, .. } => Some(payload),
-            Event::Error { payload, .. } => Some(json!({"errors": payload})),
+            Event::Next { id, payload } => Some((id, Ok(payload))),
+            Event::Error { id, payload } => Some((id, Err(Error::Operation(payload)))),
             _ => None,
         }
     }
 }
 
 impl KeepAliveSettings {
-    fn report_timeout(&self) -> Next {
+    fn report_timeout(&self) -> Message {
         warning!(
             "No messages received within keep-alive ({:?}s) from server. Closing the connection",
             self.interval.unwrap()
         );
-        Next::Command(ConnectionCommand::Close(
-            4503,
-            "Service unavailable. keep-alive failure".to_string(),
-        ))
+        Message::Close {
+            code: Some(CloseCode::KeepAliveFailure),
+            reason: Some("Service unavailable. keep-alive failure".to_string()),
+        }
     }
 }
@@ -1,19 +1,29 @@
 use std::{
     future::{Future, IntoFuture},
+    sync::{Arc, atomic::AtomicUsize},
     time::Duration,
 };
 
 use futures_lite::future;
 use serde::Serialize;
 
-use crate::{Error, graphql::GraphqlOperation, logging::trace, protocol::Event};
+use crate::{Error, Protocol, graphql::GraphqlOperation};
 
 use super::{
-    Client, Subscription,
-    actor::ConnectionActor,
-    connection::{Connection, Message, ObjectSafeConnection},
+    Client, ClientSettings, Subscription, SubscriptionId,
+    actor::{ActorSettings, ConnectionActor},
+    conection_id::IdGenerator,
+    connection::{Connection, ObjectSafeConnection},
+    events::{ConnectionEvent, EventSender},
+    handshake::{InitPayload, handshake},
     keepalive::KeepAliveSettings,
+    latency::LatencyTracker,
+    malformed::{MalformedMessagePolicy, MalformedMessages},
+    overflow::OverflowPolicy,
+    persisted::PersistedQueries,
+    ping::PingPayloads,
     production_future::read_from_producer,
+    reconnect::ReconnectSettings,
 };
 
 /// Builder for Graphql over Websocket clients
@@ -33,10 +43,18 @@ use super::{
 /// ```
 #[must_use]
 pub struct ClientBuilder {
-    payload: Option<serde_json::Value>,
+    payload: InitPayload,
     subscription_buffer_size: Option<usize>,
+    overflow_policy: OverflowPolicy,
+    id_generator: IdGenerator,
     connection: Box<dyn ObjectSafeConnection>,
     keep_alive: KeepAliveSettings,
+    reconnect: ReconnectSettings,
+    init_timeout: Option<Duration>,
+    protocol: Protocol,
+    persisted_queries: Option<PersistedQueries>,
+    pings: PingPayloads,
+    malformed: MalformedMessages,
 }
 
 impl super::Client {
@@ -56,10 +74,18 @@ impl super::Client {
         Conn: Connection + Send + 'static,
     {
         ClientBuilder {
-            payload: None,
+            payload: InitPayload::default(),
             subscription_buffer_size: None,
+            overflow_policy: OverflowPolicy::default(),
+            id_generator: IdGenerator::default(),
             connection: Box::new(connection),
             keep_alive: KeepAliveSettings::default(),
+            reconnect: ReconnectSettings::default(),
+            init_timeout: None,
+            protocol: Protocol::default(),
+            persisted_queries: None,
+            pings: PingPayloads::default(),
+            malformed: MalformedMessages::default(),
         }
     }
 }
@@ -75,7 +101,7 @@ impl ClientBuilder {
         NewPayload: Serialize,
     {
         Ok(ClientBuilder {
-            payload: Some(
+            payload: InitPayload::fixed(
                 serde_json::to_value(payload)
                     .map_err(|error| Error::Serializing(error.to_string()))?,
             ),
@@ -83,15 +109,110 @@ impl ClientBuilder {
         })
     }
 
+    /// Computes the payload of `connection_init` with `provider`
+    ///
+    /// The provider is called every time the client connects, including when
+    /// [reconnecting](ClientBuilder::reconnect), so it can be used to provide
+    /// values that expire, such as auth tokens.
+    ///
+    /// ```rust
+    /// use graphql_ws_client::Client;
+    /// # async fn fetch_token() -> String { String::new() }
+    /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+    /// # let connection = graphql_ws_client::__doc_utils::Conn;
+    /// let (client, actor) = Client::build(connection)
+    ///     .payload_provider(|| async {
+    ///         serde_json::json!({ "token": fetch_token().await })
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn payload_provider<Provider, Fut>(self, provider: Provider) -> Self
+    where
+        Provider: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = serde_json::Value> + Send + 'static,
+    {
+        ClientBuilder {
+            payload: InitPayload::provider(provider),
+            ..self
+        }
+    }
+
     /// Sets the size of the incoming message buffer that subscriptions created by this client will
     /// use
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new` is 0.
     pub fn subscription_buffer_size(self, new: usize) -> Self {
+        assert!(
+            new > 0,
+            "a subscription buffer must hold at least one message"
+        );
         ClientBuilder {
             subscription_buffer_size: Some(new),
             ..self
         }
     }
 
+    /// Sets what subscriptions created by this client do when their buffer is full.
+    ///
+    /// This defaults to [`OverflowPolicy::Block`], which stops the actor until
+    /// the subscription is read from.
+    ///
+    /// ```rust
+    /// use graphql_ws_client::{Client, OverflowPolicy};
+    /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+    /// # let connection = graphql_ws_client::__doc_utils::Conn;
+    /// let (client, actor) = Client::build(connection)
+    ///     .overflow_policy(OverflowPolicy::DropOldest)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn overflow_policy(self, overflow_policy: OverflowPolicy) -> Self {
+        ClientBuilder {
+            overflow_policy,
+            ..self
+        }
+    }
+
+    /// Sets a function that generates the ids of subscriptions created by this client.
+    ///
+    /// By default subscriptions are numbered sequentially.  Any string can
+    /// be used as an id, but each id must be unique among the subscriptions
+    /// running on a connection, including any set with
+    /// [`SubscribeOptions::id`](crate::SubscribeOptions::id).  If a generated id
+    /// is already in use the subscription fails with
+    /// [`Error::SubscriberAlreadyExists`](crate::Error::SubscriberAlreadyExists).
+    ///
+    /// ```rust
+    /// use graphql_ws_client::Client;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+    /// # let connection = graphql_ws_client::__doc_utils::Conn;
+    /// let next_id = AtomicUsize::new(0);
+    ///
+    /// let (client, actor) = Client::build(connection)
+    ///     .subscription_id_generator(move || {
+    ///         format!("dashboard-{}", next_id.fetch_add(1, Ordering::Relaxed))
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn subscription_id_generator<Generator, Id>(self, generator: Generator) -> Self
+    where
+        Generator: Fn() -> Id + Send + Sync + 'static,
+        Id: Into<SubscriptionId>,
+    {
+        ClientBuilder {
+            id_generator: IdGenerator::new(generator),
+            ..self
+        }
+    }
+
     /// Sets the interval between keep alives.
     ///
     /// Any incoming messages automatically reset this interval so keep alives may not be sent
@@ -109,6 +230,192 @@ impl ClientBuilder {
         self
     }
 
+    /// Sets a hook that computes the payload of the pings sent for keep alives.
+    ///
+    /// The time it takes the server to reply to each ping is reported in
+    /// [`ConnectionEvent::Pong`](crate::ConnectionEvent::Pong).
+    ///
+    /// ```rust
+    /// use graphql_ws_client::Client;
+    /// use std::time::Duration;
+    /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+    /// # let connection = graphql_ws_client::__doc_utils::Conn;
+    /// let (client, actor) = Client::build(connection)
+    ///     .keep_alive_interval(Duration::from_secs(10))
+    ///     .ping_payload(|| serde_json::json!({ "region": "eu-west-1" }))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ping_payload<Hook>(mut self, hook: Hook) -> Self
+    where
+        Hook: Fn() -> serde_json::Value + Send + Sync + 'static,
+    {
+        self.pings.set_ping(hook);
+        self
+    }
+
+    /// Sets a hook that computes the payload of the pongs sent in reply to
+    /// pings from the server.
+    ///
+    /// The hook receives the payload of the ping.  By default pongs have no
+    /// payload.
+    ///
+    /// ```rust
+    /// use graphql_ws_client::Client;
+    /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+    /// # let connection = graphql_ws_client::__doc_utils::Conn;
+    /// // Echo ping payloads back to the server
+    /// let (client, actor) = Client::build(connection)
+    ///     .pong_payload(|ping_payload| ping_payload.cloned())
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn pong_payload<Hook>(mut self, hook: Hook) -> Self
+    where
+        Hook: Fn(Option<&serde_json::Value>) -> Option<serde_json::Value> + Send + Sync + 'static,
+    {
+        self.pings.set_pong(hook);
+        self
+    }
+
+    /// Sets what the actor does when the server sends a message it can't decode.
+    ///
+    /// This defaults to [`MalformedMessagePolicy::Close`], which closes the
+    /// connection with code 4857.
+    pub fn malformed_message_policy(mut self, policy: MalformedMessagePolicy) -> Self {
+        self.malformed.policy = policy;
+        self
+    }
+
+    /// Sets a hook that is called with every message from the server that
+    /// can't be decoded, along with the error decoding it.
+    ///
+    /// This is called regardless of the [`MalformedMessagePolicy`].
+    ///
+    /// ```rust
+    /// use graphql_ws_client::{Client, MalformedMessagePolicy};
+    /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+    /// # let connection = graphql_ws_client::__doc_utils::Conn;
+    /// let (client, actor) = Client::build(connection)
+    ///     .malformed_message_policy(MalformedMessagePolicy::Ignore)
+    ///     .on_malformed_message(|message, error| {
+    ///         eprintln!("skipping malformed message {message}: {error}");
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_malformed_message<Hook>(mut self, hook: Hook) -> Self
+    where
+        Hook: Fn(&str, &Error) + Send + Sync + 'static,
+    {
+        self.malformed.set_hook(hook);
+        self
+    }
+
+    /// Sets how long to wait for the server to acknowledge the `connection_init` message.
+    ///
+    /// If no `connection_ack` is received in this time the connection is closed with
+    /// code 4408 and [`Error::ConnectionInitTimeout`] is returned.  By default there is
+    /// no timeout.
+    pub fn connection_init_timeout(self, timeout: Duration) -> Self {
+        ClientBuilder {
+            init_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Sets the protocol that the client will speak.
+    ///
+    /// This defaults to [`Protocol::GraphqlTransportWs`].  Note that the connection must
+    /// have been opened with the matching websocket subprotocol, which is available
+    /// from [`Protocol::subprotocol`].
+    ///
+    /// ```rust
+    /// use graphql_ws_client::{Client, Protocol};
+    /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+    /// # let connection = graphql_ws_client::__doc_utils::Conn;
+    /// let (client, actor) = Client::build(connection)
+    ///     .protocol(Protocol::SubscriptionsTransportWs)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn protocol(self, protocol: Protocol) -> Self {
+        ClientBuilder { protocol, ..self }
+    }
+
+    /// Sends operations as persisted queries rather than full query text.
+    ///
+    /// ```rust
+    /// use graphql_ws_client::{Client, PersistedQueries};
+    /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+    /// # let connection = graphql_ws_client::__doc_utils::Conn;
+    /// let (client, actor) = Client::build(connection)
+    ///     .persisted_queries(PersistedQueries::automatic())
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn persisted_queries(self, persisted_queries: PersistedQueries) -> Self {
+        ClientBuilder {
+            persisted_queries: Some(persisted_queries),
+            ..self
+        }
+    }
+
+    /// Enables automatic reconnection, using `factory` to open new connections.
+    ///
+    /// When enabled, the [`ConnectionActor`] will open a new connection if the current
+    /// one drops, is closed by the server or fails its keep alives.  It then repeats the
+    /// `connection_init` handshake and restarts any subscriptions that were still running,
+    /// so the streams returned by [`Client::subscribe`] carry on receiving messages.
+    ///
+    /// Closes that mean the server rejected the client, i.e. 44xx codes other than
+    /// 4408 & 4499, aren't retried, as the server would only reject the client again.
+    ///
+    /// ```rust
+    /// use graphql_ws_client::Client;
+    /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+    /// # let connection = graphql_ws_client::__doc_utils::Conn;
+    /// let (client, actor) = Client::build(connection)
+    ///     .reconnect(|| async {
+    ///         // Open a new websocket connection here
+    ///         # Ok(graphql_ws_client::__doc_utils::Conn)
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reconnect<Factory, Fut, Conn>(mut self, factory: Factory) -> Self
+    where
+        Factory: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Conn, Error>> + Send + 'static,
+        Conn: Connection + Send + 'static,
+    {
+        self.reconnect.set_factory(factory);
+        self
+    }
+
+    /// The number of attempts to make at re-establishing a dropped connection before
+    /// giving up and shutting down the [`ConnectionActor`].
+    ///
+    /// This defaults to 3, but has no effect if `reconnect` is not called.
+    pub fn reconnect_attempts(mut self, count: usize) -> Self {
+        self.reconnect.attempts = count;
+        self
+    }
+
+    /// Sets how long to wait before each reconnection attempt.
+    ///
+    /// This defaults to 1 second, but has no effect if `reconnect` is not called.
+    pub fn reconnect_delay(mut self, delay: Duration) -> Self {
+        self.reconnect.delay = delay;
+        self
+    }
+
     /// Initialise a Client and use it to run a single subscription
     ///
     /// ```rust
@@ -133,7 +440,9 @@ impl ClientBuilder {
     {
         let (client, actor) = self.await?;
 
-        let actor_future = actor.into_future();
+        let actor_future = Box::pin(async move {
+            actor.await;
+        });
         let subscribe_future = client.subscribe(op);
 
         let (stream, actor_future) = run_startup(subscribe_future, actor_future).await?;
@@ -162,63 +471,63 @@ impl ClientBuilder {
         let Self {
             payload,
             subscription_buffer_size,
+            overflow_policy,
+            id_generator,
             mut connection,
             keep_alive,
+            reconnect,
+            init_timeout,
+            protocol,
+            persisted_queries,
+            pings,
+            malformed,
         } = self;
 
-        connection.send(Message::init(payload)).await?;
-
-        // wait for ack before entering receiver loop:
-        loop {
-            match connection.receive().await {
-                None => return Err(Error::Unknown("connection dropped".into())),
-                Some(Message::Close { code, reason }) => {
-                    return Err(Error::Close(
-                        code.unwrap_or_default(),
-                        reason.unwrap_or_default(),
-                    ));
-                }
-                Some(Message::Ping | Message::Pong) => {}
-                Some(message @ Message::Text(_)) => {
-                    let event = message.deserialize::<Event>()?;
-                    match event {
-                        // pings can be sent at any time
-                        Event::Ping { .. } => {
-                            connection.send(Message::graphql_pong()).await?;
-                        }
-                        Event::Pong { .. } => {}
-                        Event::ConnectionAck { .. } => {
-                            // handshake completed, ready to enter main receiver loop
-                            trace!("connection_ack received, handshake completed");
-                            break;
-                        }
-                        event => {
-                            connection
-                                .send(Message::Close {
-                                    code: Some(4950),
-                                    reason: Some("Unexpected message while waiting for ack".into()),
-                                })
-                                .await
-                                .ok();
-                            return Err(Error::Decode(format!(
-                                "expected a connection_ack or ping, got {}",
-                                event.r#type()
-                            )));
-                        }
-                    }
-                }
-            }
-        }
+        let ack_payload =
+            handshake(&mut *connection, protocol, &payload, init_timeout, &pings).await?;
+
+        let events = EventSender::default();
+        events.send(ConnectionEvent::Connected {
+            payload: ack_payload,
+        });
+
+        let latency = LatencyTracker::default();
+        let operation_count = Arc::new(AtomicUsize::new(0));
 
         let (command_sender, command_receiver) = async_channel::bounded(5);
         let (drop_sender, drop_receiver) = async_channel::unbounded();
 
-        let actor = ConnectionActor::new(connection, command_receiver, drop_receiver, keep_alive);
+        let actor = ConnectionActor::new(
+            connection,
+            command_receiver,
+            drop_receiver,
+            ActorSettings {
+                keep_alive,
+                reconnect,
+                payload,
+                init_timeout,
+                protocol,
+                pings,
+                malformed,
+            },
+            events.clone(),
+            latency.clone(),
+            Arc::clone(&operation_count),
+        );
 
         let client = Client::new_internal(
             command_sender,
             drop_sender,
-            subscription_buffer_size.unwrap_or(5),
+            ClientSettings {
+                subscription_buffer_size: subscription_buffer_size.unwrap_or(5),
+                overflow_policy,
+                id_generator,
+                protocol,
+                persisted_queries,
+            },
+            events,
+            latency,
+            operation_count,
         );
 
         Ok((client, actor))
@@ -0,0 +1,62 @@
+use crate::CloseCode;
+
+use super::outcome::{OutcomeReceiver, OutcomeSender};
+
+/// Why a subscription ended.
+///
+/// This is reported by [`Subscription::completion`](super::Subscription::completion).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CompletionReason {
+    /// The server completed the operation
+    Completed,
+    /// The server ended the operation with an `error` message
+    Errored,
+    /// The subscription was stopped or dropped by the client
+    Stopped,
+    /// The subscription's buffer filled up while using
+    /// [`OverflowPolicy::Fail`](super::OverflowPolicy::Fail)
+    Overflowed,
+    /// The client closed the connection, either deliberately or because the
+    /// server broke the protocol
+    ConnectionClosed,
+    /// The server closed the connection, and it could not be re-established
+    ServerClosed {
+        /// The close code the server sent, if any
+        code: Option<CloseCode>,
+        /// The reason the server gave, if any
+        reason: Option<String>,
+    },
+    /// The server rejected the connection with a `connection_error` message
+    ServerRejected,
+    /// The server stopped responding to keep alives, and the connection
+    /// could not be re-established
+    KeepAliveFailed,
+    /// The connection dropped, and could not be re-established
+    ConnectionLost,
+}
+
+impl CompletionReason {
+    /// Whether the subscription ended because the connection failed, rather
+    /// than because the operation itself finished.
+    ///
+    /// Subscriptions that end this way can usually be restarted on a new connection.
+    ///
+    /// The server closing the connection with a 44xx code (other than 4408
+    /// or 4499) is not a transport failure, as those mean the server rejected
+    /// the client, for example due to failed authentication, and would do so
+    /// again.
+    pub fn is_transport_failure(&self) -> bool {
+        match self {
+            CompletionReason::ServerClosed { code, .. } => {
+                !code.is_some_and(CloseCode::is_rejection)
+            }
+            CompletionReason::KeepAliveFailed | CompletionReason::ConnectionLost => true,
+            _ => false,
+        }
+    }
+}
+
+/// Held by the actor for each running operation
+pub(crate) type CompletionSender = OutcomeSender<CompletionReason>;
+
+pub(super) type CompletionReceiver = OutcomeReceiver<CompletionReason>;
@@ -1,23 +1,92 @@
-use std::num::NonZero;
+use std::{
+    borrow::Borrow,
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
-/// An opaque identifier for a subscription
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+/// An identifier for a subscription
 ///
-/// Currently this wraps a `NonZero<usize>` though that may be subject to change
-/// in the future - as a result the underlying type is not exposed publically
-pub struct SubscriptionId(NonZero<usize>);
+/// This is the id sent to the server in protocol messages.  By default the
+/// client assigns each subscription a number, but any string can be used -
+/// see [`ClientBuilder::subscription_id_generator`](super::ClientBuilder::subscription_id_generator)
+/// and [`SubscribeOptions::id`](super::SubscribeOptions::id).
+pub struct SubscriptionId(Arc<str>);
 
 impl SubscriptionId {
-    pub(super) fn new(id: usize) -> Option<Self> {
-        Some(SubscriptionId(NonZero::new(id)?))
+    /// Returns this id as a string
+    pub fn as_str(&self) -> &str {
+        &self.0
     }
+}
+
+impl fmt::Display for SubscriptionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
 
-    #[expect(clippy::inherent_to_string)] // Don't want this to be public, which implementing Display would make it.
-    pub(super) fn to_string(self) -> String {
-        self.0.to_string()
+impl From<String> for SubscriptionId {
+    fn from(id: String) -> Self {
+        SubscriptionId(id.into())
     }
+}
+
+impl From<&str> for SubscriptionId {
+    fn from(id: &str) -> Self {
+        SubscriptionId(id.into())
+    }
+}
+
+impl Borrow<str> for SubscriptionId {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Generates the ids of subscriptions that weren't given one explicitly.
+#[derive(Clone)]
+pub(crate) struct IdGenerator {
+    generate: Arc<dyn Fn() -> SubscriptionId + Send + Sync>,
+
+    /// Whether a generated id that is already in use should be skipped by
+    /// generating another.  This is only safe for generators that never
+    /// repeat an id, otherwise we could retry forever.
+    skips_used_ids: bool,
+}
+
+impl IdGenerator {
+    pub(super) fn new<Generator, Id>(generator: Generator) -> Self
+    where
+        Generator: Fn() -> Id + Send + Sync + 'static,
+        Id: Into<SubscriptionId>,
+    {
+        IdGenerator {
+            generate: Arc::new(move || generator().into()),
+            skips_used_ids: false,
+        }
+    }
+
+    pub(super) fn next(&self) -> SubscriptionId {
+        (self.generate)()
+    }
+
+    pub(super) fn skips_used_ids(&self) -> bool {
+        self.skips_used_ids
+    }
+}
 
-    pub(super) fn from_str(s: &str) -> Option<Self> {
-        SubscriptionId::new(s.parse::<usize>().ok()?)
+impl Default for IdGenerator {
+    /// Numbers subscriptions sequentially, starting from 1, skipping any
+    /// number that is already in use as an explicit id
+    fn default() -> Self {
+        let next_id = AtomicUsize::new(1);
+        IdGenerator {
+            skips_used_ids: true,
+            ..IdGenerator::new(move || next_id.fetch_add(1, Ordering::Relaxed).to_string())
+        }
     }
 }
@@ -1,7 +1,7 @@
 use std::future::Future;
 use std::pin::Pin;
 
-use crate::{Error, SubscriptionId};
+use crate::{CloseCode, Error};
 
 /// Abstraction around a websocket connection.
 ///
@@ -26,7 +26,7 @@ pub enum Message {
     /// A message that closes the connection with the given code & reason
     Close {
         /// The status code for this close message
-        code: Option<u16>,
+        code: Option<CloseCode>,
         /// Some text explaining the reason the connection is being closed
         reason: Option<String>,
     },
@@ -37,38 +37,12 @@ pub enum Message {
 }
 
 impl Message {
-    pub(crate) fn deserialize<T>(self) -> Result<T, Error>
-    where
-        T: serde::de::DeserializeOwned,
-    {
-        let Message::Text(text) = self else {
-            panic!("Don't call deserialize on non-text messages");
-        };
-
-        serde_json::from_str(&text).map_err(|error| Error::Decode(error.to_string()))
-    }
-
     pub(crate) fn init(payload: Option<serde_json::Value>) -> Self {
         Self::Text(
             serde_json::to_string(&crate::protocol::ConnectionInit::new(payload))
                 .expect("payload is already serialized so this shouldn't fail"),
         )
     }
-
-    pub(crate) fn graphql_pong() -> Self {
-        Self::Text(serde_json::to_string(&crate::protocol::Message::Pong::<()>).unwrap())
-    }
-
-    pub(crate) fn graphql_ping() -> Self {
-        Self::Text(serde_json::to_string(&crate::protocol::Message::Ping::<()>).unwrap())
-    }
-
-    pub(crate) fn complete(id: SubscriptionId) -> Self {
-        Self::Text(
-            serde_json::to_string(&crate::protocol::Message::Complete::<()> { id: id.to_string() })
-                .unwrap(),
-        )
-    }
 }
 
 /// An object safe wrapper around the Connection trait, allowing us
@@ -0,0 +1,128 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures_lite::{StreamExt, stream};
+use serde_json::Value;
+
+use crate::CloseCode;
+
+/// The number of events buffered for each [`Client::events`](super::Client::events)
+/// stream before the oldest are dropped
+pub(super) const EVENT_BUFFER_SIZE: usize = 32;
+
+/// A lifecycle event for the connection behind a [`Client`](super::Client).
+///
+/// These can be received from [`Client::events`](super::Client::events).
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConnectionEvent {
+    /// The connection has been established and acknowledged by the server.
+    Connected {
+        /// The payload of the servers `connection_ack` message
+        payload: Option<Value>,
+    },
+    /// A ping was received from the server
+    Ping {
+        /// The payload of the ping
+        payload: Option<Value>,
+    },
+    /// A pong was received from the server
+    Pong {
+        /// The payload of the pong
+        payload: Option<Value>,
+        /// The time between sending a ping and receiving this pong, if this
+        /// pong was in reply to one of our pings
+        round_trip_time: Option<Duration>,
+    },
+    /// The server did not reply to keep alives, so the connection was closed
+    KeepAliveFailed,
+    /// The server closed the connection
+    ServerClosed {
+        /// The close code the server sent
+        code: Option<CloseCode>,
+        /// The reason the server gave for closing
+        reason: Option<String>,
+    },
+    /// The connection dropped and the client is trying to reconnect
+    Reconnecting {
+        /// The number of this reconnection attempt, starting from 1
+        attempt: usize,
+    },
+    /// The [`ConnectionActor`](super::ConnectionActor) has shut down, and no
+    /// more events will be sent
+    Shutdown,
+}
+
+/// Sends [`ConnectionEvent`]s to every stream created by
+/// [`Client::events`](super::Client::events)
+#[derive(Clone, Default)]
+pub(crate) struct EventSender {
+    inner: Arc<Mutex<Listeners>>,
+}
+
+#[derive(Default)]
+struct Listeners {
+    senders: Vec<async_channel::Sender<ConnectionEvent>>,
+
+    /// The most recent `Connected` event, if the connection is currently up.
+    ///
+    /// This is sent to new listeners so they can see the current state.
+    connected: Option<ConnectionEvent>,
+
+    /// The payload of the most recent `connection_ack`
+    ack_payload: Option<Value>,
+
+    shut_down: bool,
+}
+
+impl EventSender {
+    pub(super) fn subscribe(&self) -> stream::Boxed<ConnectionEvent> {
+        let (sender, receiver) = async_channel::bounded(EVENT_BUFFER_SIZE);
+
+        let mut listeners = self.inner.lock().unwrap();
+        if listeners.shut_down {
+            sender.try_send(ConnectionEvent::Shutdown).ok();
+            return receiver.boxed();
+        }
+        if let Some(connected) = &listeners.connected {
+            sender.try_send(connected.clone()).ok();
+        }
+        listeners.senders.push(sender);
+
+        receiver.boxed()
+    }
+
+    pub(super) fn ack_payload(&self) -> Option<Value> {
+        self.inner.lock().unwrap().ack_payload.clone()
+    }
+
+    pub(super) fn send(&self, event: ConnectionEvent) {
+        let mut listeners = self.inner.lock().unwrap();
+        match &event {
+            ConnectionEvent::Connected { payload } => {
+                listeners.ack_payload = payload.clone();
+                listeners.connected = Some(event.clone());
+            }
+            ConnectionEvent::KeepAliveFailed
+            | ConnectionEvent::ServerClosed { .. }
+            | ConnectionEvent::Reconnecting { .. } => listeners.connected = None,
+            ConnectionEvent::Ping { .. } | ConnectionEvent::Pong { .. } => {}
+            ConnectionEvent::Shutdown => {
+                listeners.connected = None;
+                listeners.shut_down = true;
+            }
+        }
+
+        // If a listener has fallen behind we drop its oldest event rather than
+        // buffering without limit.  Sending only fails if the stream has been
+        // dropped, so we stop sending to it
+        listeners
+            .senders
+            .retain(|sender| sender.force_send(event.clone()).is_ok());
+
+        if listeners.shut_down {
+            listeners.senders.clear();
+        }
+    }
+}
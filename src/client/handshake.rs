@@ -0,0 +1,137 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use futures_lite::{FutureExt, future};
+use serde_json::Value;
+
+use crate::{
+    CloseCode, Error,
+    logging::trace,
+    protocol::{Event, Protocol},
+};
+
+use super::{
+    connection::{Message, ObjectSafeConnection},
+    ping::PingPayloads,
+};
+
+/// Produces the payload for `connection_init` messages.
+///
+/// This is called for every handshake, so a provider can compute a fresh
+/// payload (e.g. a new auth token) each time the client connects.
+#[derive(Clone, Default)]
+pub(super) struct InitPayload(Option<Arc<dyn Fn() -> future::Boxed<Value> + Send + Sync>>);
+
+impl InitPayload {
+    pub(super) fn fixed(payload: Value) -> Self {
+        InitPayload::provider(move || future::ready(payload.clone()))
+    }
+
+    pub(super) fn provider<Provider, Fut>(provider: Provider) -> Self
+    where
+        Provider: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Value> + Send + 'static,
+    {
+        InitPayload(Some(Arc::new(move || provider().boxed())))
+    }
+
+    async fn get(&self) -> Option<Value> {
+        match &self.0 {
+            Some(provider) => Some(provider().await),
+            None => None,
+        }
+    }
+}
+
+/// Runs the `connection_init` / `connection_ack` handshake on a freshly
+/// opened connection, returning the payload of the `connection_ack`.
+///
+/// This is used when the client is first built and again whenever the
+/// actor re-establishes a dropped connection.
+pub(super) async fn handshake(
+    connection: &mut dyn ObjectSafeConnection,
+    protocol: Protocol,
+    payload: &InitPayload,
+    timeout: Option<Duration>,
+    pings: &PingPayloads,
+) -> Result<Option<Value>, Error> {
+    connection.send(Message::init(payload.get().await)).await?;
+
+    let Some(timeout) = timeout else {
+        return wait_for_ack(connection, protocol, pings).await;
+    };
+
+    let result = wait_for_ack(connection, protocol, pings)
+        .or(async {
+            futures_timer::Delay::new(timeout).await;
+            Err(Error::ConnectionInitTimeout)
+        })
+        .await;
+
+    if let Err(Error::ConnectionInitTimeout) = result {
+        connection
+            .send(Message::Close {
+                code: Some(CloseCode::ConnectionInitialisationTimeout),
+                reason: Some("Connection initialisation timeout".into()),
+            })
+            .await
+            .ok();
+    }
+
+    result
+}
+
+async fn wait_for_ack(
+    connection: &mut dyn ObjectSafeConnection,
+    protocol: Protocol,
+    pings: &PingPayloads,
+) -> Result<Option<Value>, Error> {
+    // wait for ack before entering receiver loop:
+    loop {
+        match connection.receive().await {
+            None => return Err(Error::Unknown("connection dropped".into())),
+            Some(Message::Close { code, reason }) => {
+                return Err(Error::Close(
+                    code.unwrap_or(CloseCode::NoStatusReceived),
+                    reason.unwrap_or_default(),
+                ));
+            }
+            Some(Message::Ping | Message::Pong) => {}
+            Some(Message::Text(text)) => {
+                let event = protocol.decode(&text)?;
+                match event {
+                    // pings can be sent at any time
+                    Event::Ping { payload } => {
+                        if let Some(pong) = protocol.pong(pings.pong(payload.as_ref())) {
+                            connection.send(pong).await?;
+                        }
+                    }
+                    Event::Pong { .. } => {}
+                    Event::ConnectionError { payload } => {
+                        return Err(Error::Custom(
+                            "connection_error".into(),
+                            payload.unwrap_or_default().to_string(),
+                        ));
+                    }
+                    Event::ConnectionAck { payload } => {
+                        // handshake completed, ready to enter main receiver loop
+                        trace!("connection_ack received, handshake completed");
+                        return Ok(payload);
+                    }
+                    event => {
+                        connection
+                            .send(Message::Close {
+                                code: Some(CloseCode::UnexpectedMessage),
+                                reason: Some("Unexpected message while waiting for ack".into()),
+                            })
+                            .await
+                            .ok();
+                        return Err(Error::Decode(format!(
+                            "expected a connection_ack or ping, got {}",
+                            event.r#type()
+                        )));
+                    }
+                }
+            }
+        }
+    }
+}
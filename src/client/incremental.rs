@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+/// Merges incremental delivery (`@defer` & `@stream`) payloads into complete
+/// responses.
+///
+/// Incremental payloads are identified by their `hasNext` field.  The initial
+/// payload is held onto and the `incremental` entries of each subsequent payload
+/// are merged into it, until a payload with `hasNext: false` completes the response.
+/// Payloads without `hasNext` are passed straight through.
+///
+/// Both the older format, where each `incremental` entry has a `path`, and the
+/// newer one, where entries refer to the `id` of an earlier `pending` entry, are
+/// supported.
+#[derive(Default)]
+pub(super) struct IncrementalResponse {
+    response: Option<Map<String, Value>>,
+
+    /// The paths of the `pending` entries that have been announced, by id
+    pending: HashMap<String, Vec<Value>>,
+}
+
+impl IncrementalResponse {
+    /// Applies a payload, returning the complete response if there is one
+    pub(super) fn apply(&mut self, payload: Value) -> Option<Value> {
+        let Value::Object(mut payload) = payload else {
+            return Some(payload);
+        };
+
+        let Some(has_next) = payload.remove("hasNext") else {
+            return Some(Value::Object(payload));
+        };
+
+        let incremental = payload.remove("incremental");
+
+        if let Some(Value::Array(pending)) = payload.remove("pending") {
+            for pending in pending {
+                if let (Some(Value::String(id)), Some(Value::Array(path))) =
+                    (pending.get("id"), pending.get("path"))
+                {
+                    self.pending.insert(id.clone(), path.clone());
+                }
+            }
+        }
+        let completed = payload.remove("completed");
+
+        let response = match self.response.as_mut() {
+            None => self.response.insert(payload),
+            Some(response) => {
+                merge_errors(response, payload.remove("errors"));
+                if let Some(extensions) = payload.remove("extensions") {
+                    let existing = response
+                        .entry("extensions")
+                        .or_insert_with(|| Value::Object(Map::new()));
+                    deep_merge(existing, extensions);
+                }
+                response
+            }
+        };
+
+        if let Some(Value::Array(incremental)) = incremental {
+            for patch in incremental {
+                apply_patch(response, &self.pending, patch);
+            }
+        }
+
+        // Errors for deferred or streamed fields that failed as a whole are
+        // reported when they complete
+        if let Some(Value::Array(completed)) = completed {
+            for mut completed in completed {
+                merge_errors(response, completed.get_mut("errors").map(Value::take));
+                if let Some(Value::String(id)) = completed.get("id") {
+                    self.pending.remove(id);
+                }
+            }
+        }
+
+        if has_next.as_bool().unwrap_or(false) {
+            return None;
+        }
+
+        self.pending.clear();
+        self.response.take().map(Value::Object)
+    }
+
+    /// Discards any partial response, e.g. when the operation has failed
+    pub(super) fn reset(&mut self) {
+        self.response = None;
+        self.pending.clear();
+    }
+}
+
+/// Applies a single entry of an `incremental` array to a response
+fn apply_patch(
+    response: &mut Map<String, Value>,
+    pending: &HashMap<String, Vec<Value>>,
+    patch: Value,
+) {
+    let Value::Object(mut patch) = patch else {
+        return;
+    };
+
+    merge_errors(response, patch.remove("errors"));
+
+    // Newer payloads give the id of a pending entry, with an optional subPath
+    // for deferred data, rather than the full path
+    let pending_path = match patch.remove("id") {
+        Some(Value::String(id)) => {
+            let Some(path) = pending.get(&id) else {
+                return;
+            };
+            Some(path.clone())
+        }
+        _ => None,
+    };
+
+    let Some(data) = response.get_mut("data") else {
+        return;
+    };
+
+    if let Some(Value::Array(items)) = patch.remove("items") {
+        let (list_path, start) = match pending_path {
+            // Items for a pending stream are appended to the list at its path
+            Some(path) => (path, None),
+            // Otherwise the last path segment is the index of the first item
+            None => {
+                let Some(Value::Array(mut path)) = patch.remove("path") else {
+                    return;
+                };
+                let Some(Value::Number(start)) = path.pop() else {
+                    return;
+                };
+                let Some(start) = start.as_u64().and_then(|start| usize::try_from(start).ok())
+                else {
+                    return;
+                };
+                (path, Some(start))
+            }
+        };
+        let Some(Value::Array(list)) = value_at(data, &list_path) else {
+            return;
+        };
+        let start = start.unwrap_or(list.len());
+        for (index, item) in (start..).zip(items) {
+            match list.get_mut(index) {
+                Some(existing) => *existing = item,
+                None => list.push(item),
+            }
+        }
+    } else if let Some(patch_data) = patch.remove("data") {
+        let path = match (pending_path, patch.remove("subPath"), patch.remove("path")) {
+            (Some(mut path), Some(Value::Array(sub_path)), _) => {
+                path.extend(sub_path);
+                path
+            }
+            (Some(path), _, _) | (None, _, Some(Value::Array(path))) => path,
+            (None, _, _) => Vec::new(),
+        };
+        if let Some(target) = value_at(data, &path) {
+            deep_merge(target, patch_data);
+        }
+    }
+}
+
+fn value_at<'a>(mut value: &'a mut Value, path: &[Value]) -> Option<&'a mut Value> {
+    for segment in path {
+        value = match segment {
+            Value::String(key) => value.get_mut(key.as_str())?,
+            Value::Number(index) => value.get_mut(usize::try_from(index.as_u64()?).ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+fn deep_merge(target: &mut Value, source: Value) {
+    match (target, source) {
+        (Value::Object(target), Value::Object(source)) => {
+            for (key, value) in source {
+                match target.get_mut(&key) {
+                    Some(existing) => deep_merge(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (_, Value::Null) => {}
+        (target, source) => *target = source,
+    }
+}
+
+fn merge_errors(response: &mut Map<String, Value>, errors: Option<Value>) {
+    let Some(Value::Array(errors)) = errors else {
+        return;
+    };
+    if errors.is_empty() {
+        return;
+    }
+
+    match response
+        .entry("errors")
+        .or_insert_with(|| Value::Array(Vec::new()))
+    {
+        Value::Array(existing) => existing.extend(errors),
+        other => *other = Value::Array(errors),
+    }
+}
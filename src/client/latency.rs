@@ -0,0 +1,74 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// The number of samples that [`LatencySnapshot::average`] is calculated over
+const AVERAGE_WINDOW: usize = 10;
+
+/// Round trip latency measurements for a connection, taken from keep alive
+/// pings.
+///
+/// This can be retrieved with [`Client::latency`](super::Client::latency).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LatencySnapshot {
+    /// The most recent round trip time
+    pub last: Duration,
+    /// The shortest round trip time seen since the client was built
+    pub min: Duration,
+    /// The longest round trip time seen since the client was built
+    pub max: Duration,
+    /// The average of the most recent round trip times
+    pub average: Duration,
+    /// The total number of round trips that have been measured
+    pub samples: usize,
+}
+
+/// Records round trip times, shared between the actor & its clients
+#[derive(Clone, Default)]
+pub(crate) struct LatencyTracker {
+    inner: Arc<Mutex<LatencyStats>>,
+}
+
+#[derive(Default)]
+struct LatencyStats {
+    recent: VecDeque<Duration>,
+    min: Duration,
+    max: Duration,
+    samples: usize,
+}
+
+impl LatencyTracker {
+    pub(super) fn record(&self, round_trip_time: Duration) {
+        let mut stats = self.inner.lock().unwrap();
+
+        if stats.samples == 0 {
+            stats.min = round_trip_time;
+            stats.max = round_trip_time;
+        }
+        stats.min = stats.min.min(round_trip_time);
+        stats.max = stats.max.max(round_trip_time);
+        stats.samples += 1;
+
+        if stats.recent.len() == AVERAGE_WINDOW {
+            stats.recent.pop_front();
+        }
+        stats.recent.push_back(round_trip_time);
+    }
+
+    pub(super) fn snapshot(&self) -> Option<LatencySnapshot> {
+        let stats = self.inner.lock().unwrap();
+
+        let last = *stats.recent.back()?;
+        let total = stats.recent.iter().sum::<Duration>();
+
+        Some(LatencySnapshot {
+            last,
+            min: stats.min,
+            max: stats.max,
+            average: total / stats.recent.len() as u32,
+            samples: stats.samples,
+        })
+    }
+}
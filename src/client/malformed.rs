@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use crate::{CloseCode, Error, logging::warning};
+
+use super::Message;
+
+type MalformedMessageHook = Arc<dyn Fn(&str, &Error) + Send + Sync>;
+
+/// What the actor should do when the server sends a message it can't decode.
+///
+/// This only covers messages that can't be decoded at all: invalid JSON and
+/// messages with an unknown `type`.  Well formed messages that end the
+/// connection, such as a legacy `connection_error`, aren't affected by it.
+///
+/// This can be set with [`ClientBuilder::malformed_message_policy`](super::ClientBuilder::malformed_message_policy).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MalformedMessagePolicy {
+    /// Close the connection with code 4857, ending every subscription
+    #[default]
+    Close,
+    /// Skip the message and keep the connection open
+    Ignore,
+}
+
+/// How the actor handles malformed messages
+#[derive(Clone, Default)]
+pub(super) struct MalformedMessages {
+    pub(super) policy: MalformedMessagePolicy,
+    hook: Option<MalformedMessageHook>,
+}
+
+impl MalformedMessages {
+    pub(super) fn set_hook<Hook>(&mut self, hook: Hook)
+    where
+        Hook: Fn(&str, &Error) + Send + Sync + 'static,
+    {
+        self.hook = Some(Arc::new(hook));
+    }
+
+    /// Reports a message that couldn't be decoded, returning the message to
+    /// close the connection with if the policy requires it.
+    pub(super) fn handle(&self, text: &str, error: &Error) -> Option<Message> {
+        if let Some(hook) = &self.hook {
+            hook(text, error);
+        }
+
+        match self.policy {
+            MalformedMessagePolicy::Close => Some(Message::Close {
+                code: Some(CloseCode::DecodeError),
+                reason: Some(format!("Error while decoding event: {error}")),
+            }),
+            MalformedMessagePolicy::Ignore => {
+                warning!("Ignoring message that couldn't be decoded: {error}");
+                None
+            }
+        }
+    }
+}
@@ -4,39 +4,64 @@ use std::{
         Arc,
         atomic::{AtomicUsize, Ordering},
     },
+    time::Duration,
 };
 
-use futures_lite::StreamExt;
+use futures_lite::{Stream, StreamExt};
 use serde_json::Value;
 
-use crate::{
-    Error,
-    graphql::GraphqlOperation,
-    protocol::{self},
-};
+use crate::{CloseCode, Error, Protocol, graphql::GraphqlOperation};
 
 mod actor;
 mod builder;
+mod completion;
 mod conection_id;
 mod connection;
+mod events;
+mod handshake;
+mod incremental;
 mod keepalive;
+mod latency;
+mod malformed;
+mod options;
+mod outcome;
+mod overflow;
+mod persisted;
+mod ping;
+mod pool;
 mod production_future;
+mod reconnect;
+mod shutdown;
 mod subscription;
 
 pub use self::{
     actor::ConnectionActor,
     builder::ClientBuilder,
+    completion::CompletionReason,
     conection_id::SubscriptionId,
     connection::{Connection, Message},
+    events::ConnectionEvent,
+    latency::LatencySnapshot,
+    malformed::MalformedMessagePolicy,
+    options::SubscribeOptions,
+    overflow::OverflowPolicy,
+    persisted::PersistedQueries,
+    pool::{ClientPool, ClientPoolBuilder},
+    shutdown::{ActorHandle, ShutdownReason},
     subscription::Subscription,
 };
 
+use self::{
+    completion::CompletionSender, conection_id::IdGenerator, events::EventSender,
+    incremental::IncrementalResponse, latency::LatencyTracker,
+};
+
 /// A GraphQL over Websocket client
 ///
 /// ```rust,no_run
 /// use graphql_ws_client::Client;
 /// use std::future::IntoFuture;
-/// use futures_lite::StreamExt;
+/// use futures_lite::{Stream, StreamExt};
 /// # use graphql_ws_client::__doc_utils::spawn;
 /// # async fn example() -> Result<(), graphql_ws_client::Error> {
 /// # let connection = graphql_ws_client::__doc_utils::Conn;
@@ -57,27 +82,63 @@ pub use self::{
 #[derive(Clone)]
 pub struct Client {
     actor: async_channel::Sender<ConnectionCommand>,
-    drop_sender: async_channel::Sender<SubscriptionId>,
+    drop_sender: async_channel::Sender<ConnectionCommand>,
     subscription_buffer_size: usize,
-    next_id: Arc<AtomicUsize>,
+    overflow_policy: OverflowPolicy,
+    id_generator: IdGenerator,
+    protocol: Protocol,
+    persisted_queries: Option<PersistedQueries>,
+    events: EventSender,
+    latency: LatencyTracker,
+    operation_count: Arc<AtomicUsize>,
+}
+
+/// The settings from a `ClientBuilder` that the client makes use of
+pub(super) struct ClientSettings {
+    pub(super) subscription_buffer_size: usize,
+    pub(super) overflow_policy: OverflowPolicy,
+    pub(super) id_generator: IdGenerator,
+    pub(super) protocol: Protocol,
+    pub(super) persisted_queries: Option<PersistedQueries>,
 }
 
 impl Client {
     pub(super) fn new_internal(
         actor: async_channel::Sender<ConnectionCommand>,
-        drop_sender: async_channel::Sender<SubscriptionId>,
-        subscription_buffer_size: usize,
+        drop_sender: async_channel::Sender<ConnectionCommand>,
+        settings: ClientSettings,
+        events: EventSender,
+        latency: LatencyTracker,
+        operation_count: Arc<AtomicUsize>,
     ) -> Self {
+        let ClientSettings {
+            subscription_buffer_size,
+            overflow_policy,
+            id_generator,
+            protocol,
+            persisted_queries,
+        } = settings;
+
         Client {
             actor,
             drop_sender,
             subscription_buffer_size,
-            next_id: Arc::new(AtomicUsize::new(1)),
+            overflow_policy,
+            id_generator,
+            protocol,
+            persisted_queries,
+            events,
+            latency,
+            operation_count,
         }
     }
 
     /// Starts a streaming operation on this client.
     ///
+    /// If the server uses incremental delivery for `@defer` or `@stream`, its
+    /// payloads are merged into a single response.  Nothing is emitted for
+    /// that response until the server sends the payload with `hasNext: false`.
+    ///
     /// Returns a `Stream` of responses.
     pub async fn subscribe<Operation>(
         &self,
@@ -86,60 +147,300 @@ impl Client {
     where
         Operation: GraphqlOperation + Unpin + Send + 'static,
     {
-        let (sender, receiver) = async_channel::bounded(self.subscription_buffer_size);
+        self.subscribe_with(op, SubscribeOptions::default()).await
+    }
 
-        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+    /// Starts a streaming operation on this client, overriding the client's
+    /// settings for this subscription.
+    ///
+    /// ```rust,no_run
+    /// use graphql_ws_client::{OverflowPolicy, SubscribeOptions};
+    /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+    /// # let connection = graphql_ws_client::__doc_utils::Conn;
+    /// # let subscription = graphql_ws_client::__doc_utils::Subscription;
+    /// # let (client, _) = graphql_ws_client::Client::build(connection).await?;
+    /// let stream = client
+    ///     .subscribe_with(
+    ///         subscription,
+    ///         SubscribeOptions::default()
+    ///             .buffer_size(1000)
+    ///             .overflow_policy(OverflowPolicy::DropOldest),
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Returns a `Stream` of responses.
+    pub async fn subscribe_with<Operation>(
+        &self,
+        op: Operation,
+        options: SubscribeOptions,
+    ) -> Result<Subscription<Operation>, Error>
+    where
+        Operation: GraphqlOperation + Unpin + Send + 'static,
+    {
+        let actor = self.actor.clone();
 
-        let message = protocol::Message::Subscribe {
-            id: id.to_string(),
-            payload: &op,
-        };
+        let (id, receiver, completion, reading) = loop {
+            let (sender, receiver) = async_channel::bounded(
+                options.buffer_size.unwrap_or(self.subscription_buffer_size),
+            );
 
-        let request = serde_json::to_string(&message)
-            .map_err(|error| Error::Serializing(error.to_string()))?;
+            let id = match &options.id {
+                Some(id) => id.clone(),
+                None => self.id_generator.next(),
+            };
 
-        let id = SubscriptionId::new(id).ok_or(Error::ConnectionIdsExhausted)?;
+            let (request, fallback) = match &self.persisted_queries {
+                None => (self.protocol.subscribe(id.to_string(), &op)?, None),
+                Some(persisted_queries) => {
+                    let payload = serde_json::to_value(&op)
+                        .map_err(|error| Error::Serializing(error.to_string()))?;
+                    let (payload, fallback) = persisted_queries.rewrite(payload);
+                    let fallback = fallback
+                        .map(|fallback| self.protocol.subscribe(id.to_string(), &fallback))
+                        .transpose()?;
+                    (self.protocol.subscribe(id.to_string(), &payload)?, fallback)
+                }
+            };
 
-        let actor = self.actor.clone();
-        actor
-            .send(ConnectionCommand::Subscribe {
-                request,
-                sender,
-                id,
-            })
-            .await
-            .map_err(|error| Error::Send(error.to_string()))?;
+            let (started_sender, started) = async_channel::bounded(1);
+            let (completion_sender, completion) = outcome::outcome();
+            let (reading, drained) = async_channel::bounded(1);
+
+            actor
+                .send(ConnectionCommand::Subscribe {
+                    request,
+                    fallback,
+                    sender,
+                    overflow_policy: options.overflow_policy.unwrap_or(self.overflow_policy),
+                    id: id.clone(),
+                    started: started_sender,
+                    completion: completion_sender,
+                    drained,
+                })
+                .await
+                .map_err(|error| Error::Send(error.to_string()))?;
+
+            match started
+                .recv()
+                .await
+                .map_err(|error| Error::Send(error.to_string()))?
+            {
+                Ok(()) => break (id, receiver, completion, reading),
+                // A generated id can clash with one that was provided explicitly,
+                // in which case we move on to the next one
+                Err(Error::SubscriberAlreadyExists(_))
+                    if options.id.is_none() && self.id_generator.skips_used_ids() => {}
+                Err(error) => return Err(error),
+            }
+        };
+
+        let mut incremental = IncrementalResponse::default();
+        let responses = receiver.filter_map(move |response| match response {
+            Ok(payload) => incremental.apply(payload).map(Ok),
+            Err(error) => {
+                incremental.reset();
+                Some(Err(error))
+            }
+        });
 
         Ok(Subscription::<Operation> {
             id,
-            stream: Some(Box::pin(receiver.map(move |response| {
-                op.decode(response)
+            stream: Some(Box::pin(responses.map(move |response| {
+                op.decode(response?)
                     .map_err(|err| Error::Decode(err.to_string()))
             }))),
             actor,
             drop_sender: Some(self.drop_sender.clone()),
+            completion,
+            reading: Some(reading),
         })
     }
 
+    /// Runs a single result operation, such as a query or mutation, on this client.
+    ///
+    /// This sends the operation in the same `subscribe` message that
+    /// [`Client::subscribe`] uses, then waits for its first result.  The
+    /// operation is stopped once that result arrives, so any further results
+    /// are discarded.
+    ///
+    /// ```rust,no_run
+    /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+    /// # let connection = graphql_ws_client::__doc_utils::Conn;
+    /// # let query = graphql_ws_client::__doc_utils::Subscription;
+    /// # let (client, _) = graphql_ws_client::Client::build(connection).await?;
+    /// let response = client.execute(query).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the operation could not be started, the response
+    /// couldn't be decoded, or the operation completed without a response, in
+    /// which case [`Error::NoResponse`] is returned.
+    pub async fn execute<Operation>(&self, op: Operation) -> Result<Operation::Response, Error>
+    where
+        Operation: GraphqlOperation + Unpin + Send + 'static,
+    {
+        let mut subscription = self.subscribe(op).await?;
+
+        // Dropping the subscription stops the operation if the server hasn't
+        // already completed it.
+        subscription.next().await.ok_or(Error::NoResponse)?
+    }
+
+    /// Returns the payload of the `connection_ack` message the server sent
+    /// when this client connected.
+    ///
+    /// Servers can use this payload to send session information, negotiated
+    /// limits or feature flags.  If the client has reconnected this will be
+    /// the payload from the most recent connection.
+    ///
+    /// ```rust,no_run
+    /// use graphql_ws_client::Client;
+    /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+    /// # let connection = graphql_ws_client::__doc_utils::Conn;
+    /// let (client, actor) = Client::build(connection).await?;
+    ///
+    /// if let Some(payload) = client.ack_payload() {
+    ///     println!("session: {}", payload["session"]);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ack_payload(&self) -> Option<Value> {
+        self.events.ack_payload()
+    }
+
+    /// Returns round trip latency measurements for the connection behind this client.
+    ///
+    /// These are measured from the pings sent for keep alives, so this will
+    /// return `None` until [`ClientBuilder::keep_alive_interval`] has been
+    /// set and a ping has been answered.  Note that keep alive pings are only
+    /// sent when a connection has been idle for the keep alive interval.
+    pub fn latency(&self) -> Option<LatencySnapshot> {
+        self.latency.snapshot()
+    }
+
+    /// The number of operations currently running on the connection
+    pub(super) fn operation_count(&self) -> usize {
+        self.operation_count.load(Ordering::Relaxed)
+    }
+
+    /// Whether the actor behind this client has stopped accepting operations
+    pub(super) fn is_closed(&self) -> bool {
+        self.actor.is_closed()
+    }
+
+    /// Returns a `Stream` of lifecycle events for the connection behind this client.
+    ///
+    /// If the connection is currently up the stream will start with a
+    /// [`ConnectionEvent::Connected`], and it will end after the
+    /// [`ConnectionEvent::Shutdown`] that is sent when the [`ConnectionActor`]
+    /// stops.
+    ///
+    /// Each stream buffers up to 32 events.  If it isn't polled often enough to
+    /// keep up, the oldest events are dropped to make room for new ones.
+    ///
+    /// ```rust,no_run
+    /// use graphql_ws_client::{Client, ConnectionEvent};
+    /// use futures_lite::StreamExt;
+    /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+    /// # let connection = graphql_ws_client::__doc_utils::Conn;
+    /// let (client, actor) = Client::build(connection).await?;
+    ///
+    /// let mut events = client.events();
+    /// while let Some(event) = events.next().await {
+    ///     if let ConnectionEvent::ServerClosed { code, reason } = event {
+    ///         // Raise an alert
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn events(&self) -> impl Stream<Item = ConnectionEvent> + Send + Unpin + 'static {
+        self.events.subscribe()
+    }
+
     /// Stops a subscription by id
     ///
+    /// This resolves once the `complete` message for the subscription has
+    /// been sent to the server, after which no more responses will be
+    /// received for it.
+    ///
+    /// Note that when using [`OverflowPolicy::Block`] the actor may be waiting for
+    /// the subscription to be read from, in which case this won't resolve until it is.
+    ///
     /// # Errors
     ///
     /// Will return `Err` if the connection actor has already been shut down.
     pub async fn stop(&self, subscription_id: SubscriptionId) -> Result<(), Error> {
+        let (stopped_sender, stopped) = async_channel::bounded(1);
+
         self.actor
-            .send(ConnectionCommand::Cancel(subscription_id))
+            .send(ConnectionCommand::Cancel {
+                id: subscription_id,
+                stopped: Some(stopped_sender),
+            })
             .await
-            .map_err(|error| Error::Send(error.to_string()))
+            .map_err(|error| Error::Send(error.to_string()))?;
+
+        // The actor drops its sender once the complete has been sent
+        stopped.recv().await.ok();
+
+        Ok(())
+    }
+
+    /// Gracefully shuts down the connection, giving running subscriptions a
+    /// chance to finish.
+    ///
+    /// This stops the actor accepting new subscriptions and sends a `complete`
+    /// for every running subscription.  Each subscription stream still returns
+    /// the responses it has already received before it ends, and the connection
+    /// stays open for up to `timeout` while they are read, answering any pings
+    /// from the server.  Once every stream has been read to the end (or the
+    /// timeout passes) the connection is closed with [`CloseCode::Normal`].
+    ///
+    /// Subscriptions started while shutting down fail with
+    /// [`Error::ShuttingDown`], and calling [`Client::close`] closes the
+    /// connection without waiting any longer.
+    ///
+    /// ```rust,no_run
+    /// # use std::time::Duration;
+    /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+    /// # let connection = graphql_ws_client::__doc_utils::Conn;
+    /// # let (client, _) = graphql_ws_client::Client::build(connection).await?;
+    /// client.shutdown(Duration::from_secs(5)).await;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// This resolves once the connection has been closed.
+    pub async fn shutdown(self, timeout: Duration) {
+        let (done_sender, done) = async_channel::bounded(1);
+
+        let command = ConnectionCommand::Shutdown {
+            timeout,
+            done: done_sender,
+        };
+        if self.actor.send(command).await.is_err() {
+            return;
+        }
+
+        // The actor drops its sender once the connection is closed
+        done.recv().await.ok();
     }
 
     /// Gracefully closes the connection
     ///
     /// This will stop all running subscriptions and shut down the [`ConnectionActor`] wherever
     /// it is running.
-    pub async fn close(self, code: u16, description: impl Into<String>) {
+    pub async fn close(self, code: impl Into<CloseCode>, description: impl Into<String>) {
         self.actor
-            .send(ConnectionCommand::Close(code, description.into()))
+            .send(ConnectionCommand::Close(code.into(), description.into()))
             .await
             .ok();
     }
@@ -149,18 +450,36 @@ pub(super) enum ConnectionCommand {
     Subscribe {
         /// The full subscribe request as a JSON encoded string.
         request: String,
-        sender: async_channel::Sender<Value>,
+        /// The request to retry with if the server doesn't recognise a persisted query
+        fallback: Option<String>,
+        sender: async_channel::Sender<Result<Value, Error>>,
+        overflow_policy: OverflowPolicy,
         id: SubscriptionId,
+        /// Tells the client whether the subscription was started
+        started: async_channel::Sender<Result<(), Error>>,
+        completion: CompletionSender,
+        /// Closes once the subscription's stream has been read to the end
+        drained: async_channel::Receiver<()>,
     },
     Ping,
-    Cancel(SubscriptionId),
-    Close(u16, String),
+    Cancel {
+        id: SubscriptionId,
+        /// Dropped by the actor once the operation has been stopped
+        stopped: Option<async_channel::Sender<()>>,
+    },
+    Close(CloseCode, String),
+    Shutdown {
+        timeout: Duration,
+        /// Dropped by the actor once the connection is closed
+        done: async_channel::Sender<()>,
+    },
 }
 
 impl fmt::Debug for Client {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Client")
             .field("subscription_buffer_size", &self.subscription_buffer_size)
+            .field("protocol", &self.protocol)
             .finish_non_exhaustive()
     }
 }
@@ -0,0 +1,62 @@
+use super::{OverflowPolicy, SubscriptionId};
+
+/// Settings for a single subscription, for use with [`Client::subscribe_with`](super::Client::subscribe_with).
+///
+/// Any setting that isn't provided falls back to the client wide default set
+/// on the [`ClientBuilder`](super::ClientBuilder).
+///
+/// ```rust
+/// use graphql_ws_client::{OverflowPolicy, SubscribeOptions};
+///
+/// let options = SubscribeOptions::default()
+///     .buffer_size(100)
+///     .overflow_policy(OverflowPolicy::DropOldest);
+/// ```
+#[derive(Clone, Debug, Default)]
+#[must_use]
+pub struct SubscribeOptions {
+    pub(super) buffer_size: Option<usize>,
+    pub(super) overflow_policy: Option<OverflowPolicy>,
+    pub(super) id: Option<SubscriptionId>,
+}
+
+impl SubscribeOptions {
+    /// Sets the size of the incoming message buffer for this subscription
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer_size` is 0.
+    pub fn buffer_size(self, buffer_size: usize) -> Self {
+        assert!(
+            buffer_size > 0,
+            "a subscription buffer must hold at least one message"
+        );
+        SubscribeOptions {
+            buffer_size: Some(buffer_size),
+            ..self
+        }
+    }
+
+    /// Sets what this subscription does when its buffer is full
+    pub fn overflow_policy(self, overflow_policy: OverflowPolicy) -> Self {
+        SubscribeOptions {
+            overflow_policy: Some(overflow_policy),
+            ..self
+        }
+    }
+
+    /// Sets the id that this subscription will use in protocol messages.
+    ///
+    /// By default the client assigns ids itself.  A provided id must not be
+    /// in use by any other subscription on the connection.  The default id
+    /// generator skips any number that is in use as an explicit id, but a
+    /// custom generator set with
+    /// [`ClientBuilder::subscription_id_generator`](super::ClientBuilder::subscription_id_generator)
+    /// does not, so its ids shouldn't overlap with explicit ones.
+    pub fn id(self, id: impl Into<SubscriptionId>) -> Self {
+        SubscribeOptions {
+            id: Some(id.into()),
+            ..self
+        }
+    }
+}
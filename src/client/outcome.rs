@@ -0,0 +1,56 @@
+use std::sync::{Arc, Mutex};
+
+/// Creates the two halves of a one-off signal that carries a value.
+///
+/// The signal fires when the `OutcomeSender` is dropped, whether or not it
+/// has sent a value, and wakes every `OutcomeReceiver` waiting on it.
+pub(super) fn outcome<T>() -> (OutcomeSender<T>, OutcomeReceiver<T>) {
+    let value = Arc::new(Mutex::new(None));
+    let (sender, receiver) = async_channel::bounded(1);
+
+    (
+        OutcomeSender {
+            value: Arc::clone(&value),
+            _closed: sender,
+        },
+        OutcomeReceiver {
+            value,
+            closed: receiver,
+        },
+    )
+}
+
+pub(crate) struct OutcomeSender<T> {
+    value: Arc<Mutex<Option<T>>>,
+    _closed: async_channel::Sender<()>,
+}
+
+impl<T> OutcomeSender<T> {
+    pub(super) fn send(self, value: T) {
+        self.value.lock().unwrap().get_or_insert(value);
+    }
+}
+
+pub(crate) struct OutcomeReceiver<T> {
+    value: Arc<Mutex<Option<T>>>,
+    closed: async_channel::Receiver<()>,
+}
+
+impl<T> Clone for OutcomeReceiver<T> {
+    fn clone(&self) -> Self {
+        OutcomeReceiver {
+            value: Arc::clone(&self.value),
+            closed: self.closed.clone(),
+        }
+    }
+}
+
+impl<T: Clone> OutcomeReceiver<T> {
+    /// Waits for the sender to be dropped, returning the value it sent if any
+    pub(super) async fn wait(self) -> Option<T> {
+        // Nothing is ever sent on this channel, it just closes when the sender is dropped
+        self.closed.recv().await.ok();
+
+        self.value.lock().unwrap().clone()
+    }
+}
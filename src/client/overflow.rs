@@ -0,0 +1,67 @@
+use serde_json::Value;
+
+use crate::{Error, logging::trace};
+
+use super::CompletionReason;
+
+/// What the actor should do when a subscription's buffer is full.
+///
+/// Each subscription has a buffer of `subscription_buffer_size` responses
+/// that haven't yet been read from the [`Subscription`](super::Subscription)
+/// stream.  This policy decides what happens to new responses when a slow
+/// consumer lets that buffer fill up.
+///
+/// This can be set with [`ClientBuilder::overflow_policy`](super::ClientBuilder::overflow_policy).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait for the consumer to make room in the buffer.
+    ///
+    /// Note that the actor handles every subscription on a connection, so
+    /// while it waits no other subscriptions will receive responses and keep
+    /// alives won't be processed.
+    #[default]
+    Block,
+    /// Discard the oldest buffered response to make room for the new one
+    DropOldest,
+    /// Discard the new response, keeping everything already buffered
+    DropNewest,
+    /// End the subscription with [`Error::SubscriptionOverflow`] and stop the
+    /// operation on the server.
+    ///
+    /// The error replaces the oldest buffered response.
+    Fail,
+}
+
+impl OverflowPolicy {
+    /// Sends a payload to a subscription according to this policy.
+    ///
+    /// Returns `Err` with the reason the subscription ended if it should be stopped.
+    pub(super) async fn send(
+        self,
+        sender: &async_channel::Sender<Result<Value, Error>>,
+        payload: Result<Value, Error>,
+    ) -> Result<(), CompletionReason> {
+        let sent = match self {
+            OverflowPolicy::Block => sender.send(payload).await.is_ok(),
+            OverflowPolicy::DropOldest => sender.force_send(payload).is_ok(),
+            OverflowPolicy::DropNewest => match sender.try_send(payload) {
+                Err(async_channel::TrySendError::Full(_)) => {
+                    trace!("Subscription buffer full, dropping response");
+                    true
+                }
+                result => result.is_ok(),
+            },
+            OverflowPolicy::Fail => match sender.try_send(payload) {
+                Err(async_channel::TrySendError::Full(_)) => {
+                    trace!("Subscription buffer full, failing subscription");
+                    sender.force_send(Err(Error::SubscriptionOverflow)).ok();
+                    return Err(CompletionReason::Overflowed);
+                }
+                result => result.is_ok(),
+            },
+        };
+
+        // Sending only fails once the subscription has been dropped
+        sent.then_some(()).ok_or(CompletionReason::Stopped)
+    }
+}
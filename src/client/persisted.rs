@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::graphql::GraphqlError;
+
+type DocumentRegistry = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// Sends operations to the server by reference rather than as full query text.
+///
+/// This can be enabled with [`ClientBuilder::persisted_queries`](super::ClientBuilder::persisted_queries).
+#[derive(Clone)]
+pub struct PersistedQueries {
+    mode: Mode,
+}
+
+#[derive(Clone)]
+enum Mode {
+    #[cfg(feature = "persisted-queries")]
+    Automatic,
+    DocumentIds(DocumentRegistry),
+}
+
+impl PersistedQueries {
+    /// Uses [automatic persisted queries][1].
+    ///
+    /// Operations are sent with the SHA-256 hash of their query in
+    /// `extensions.persistedQuery` and no query text.  If the server doesn't
+    /// recognise the hash it replies with a `PersistedQueryNotFound` error,
+    /// and the operation is retried with both the hash & the full query so
+    /// the server can store it.  Operations that already have `extensions`
+    /// which aren't an object are sent with their full query instead.
+    ///
+    /// This requires the `persisted-queries` feature.
+    ///
+    /// [1]: https://www.apollographql.com/docs/apollo-server/performance/apq
+    #[cfg(feature = "persisted-queries")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "persisted-queries")))]
+    pub fn automatic() -> Self {
+        PersistedQueries {
+            mode: Mode::Automatic,
+        }
+    }
+
+    /// Uses trusted documents, sending a `documentId` in place of the query.
+    ///
+    /// `registry` is called with the query text of each operation and should
+    /// return its document id.  Operations that aren't in the registry are
+    /// sent with their full query.
+    ///
+    /// ```rust
+    /// use graphql_ws_client::PersistedQueries;
+    /// use std::collections::HashMap;
+    ///
+    /// let documents: HashMap<String, String> = HashMap::new();
+    /// let persisted_queries = PersistedQueries::document_ids(move |query| {
+    ///     documents.get(query).cloned()
+    /// });
+    /// ```
+    pub fn document_ids<Registry>(registry: Registry) -> Self
+    where
+        Registry: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        PersistedQueries {
+            mode: Mode::DocumentIds(Arc::new(registry)),
+        }
+    }
+
+    /// Rewrites the payload of a subscribe message.
+    ///
+    /// Returns the payload to send, along with the payload to retry with if
+    /// the server doesn't know about the persisted query.
+    pub(super) fn rewrite(&self, mut payload: Value) -> (Value, Option<Value>) {
+        let Some(query) = payload.get("query").and_then(Value::as_str) else {
+            return (payload, None);
+        };
+
+        match &self.mode {
+            #[cfg(feature = "persisted-queries")]
+            Mode::Automatic => {
+                let persisted_query = serde_json::json!({
+                    "version": 1,
+                    "sha256Hash": sha256_hex(query),
+                });
+
+                let Some(fields) = payload.as_object_mut() else {
+                    return (payload, None);
+                };
+                let extensions = fields.entry("extensions").or_insert(Value::Null);
+                if extensions.is_null() {
+                    *extensions = serde_json::json!({});
+                }
+
+                // Extensions we can't add to are sent as they are, with the full query
+                let Some(extensions) = extensions.as_object_mut() else {
+                    return (payload, None);
+                };
+                extensions.insert("persistedQuery".into(), persisted_query);
+
+                let full = payload.clone();
+                if let Some(payload) = payload.as_object_mut() {
+                    payload.remove("query");
+                }
+                (payload, Some(full))
+            }
+            Mode::DocumentIds(registry) => {
+                let Some(document_id) = registry(query) else {
+                    return (payload, None);
+                };
+                if let Some(payload) = payload.as_object_mut() {
+                    payload.remove("query");
+                    payload.insert("documentId".into(), document_id.into());
+                }
+                (payload, None)
+            }
+        }
+    }
+}
+
+/// Whether a `next` payload is the server rejecting an operation because it
+/// didn't recognise its hash, rather than a result
+pub(super) fn is_persisted_query_not_found_response(response: &Value) -> bool {
+    if response.get("data").is_some_and(|data| !data.is_null()) {
+        return false;
+    }
+
+    response.get("errors").is_some_and(|errors| {
+        is_persisted_query_not_found(&GraphqlError::from_payload(errors.clone()))
+    })
+}
+
+/// Whether the server rejected an operation because it didn't recognise its hash
+pub(super) fn is_persisted_query_not_found(errors: &[GraphqlError]) -> bool {
+    errors.iter().any(|error| {
+        error.message == "PersistedQueryNotFound"
+            || error
+                .extensions
+                .as_ref()
+                .and_then(|extensions| extensions.get("code"))
+                .and_then(Value::as_str)
+                == Some("PERSISTED_QUERY_NOT_FOUND")
+    })
+}
+
+#[cfg(feature = "persisted-queries")]
+fn sha256_hex(query: &str) -> String {
+    use sha2::{Digest, Sha256};
+    use std::fmt::Write;
+
+    Sha256::digest(query.as_bytes())
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            write!(hex, "{byte:02x}").unwrap();
+            hex
+        })
+}
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+type PingHook = Arc<dyn Fn() -> Value + Send + Sync>;
+type PongHook = Arc<dyn Fn(Option<&Value>) -> Option<Value> + Send + Sync>;
+
+/// Hooks for computing the payloads of protocol level pings & pongs
+#[derive(Clone, Default)]
+pub(super) struct PingPayloads {
+    ping: Option<PingHook>,
+    pong: Option<PongHook>,
+}
+
+impl PingPayloads {
+    pub(super) fn set_ping<Hook>(&mut self, hook: Hook)
+    where
+        Hook: Fn() -> Value + Send + Sync + 'static,
+    {
+        self.ping = Some(Arc::new(hook));
+    }
+
+    pub(super) fn set_pong<Hook>(&mut self, hook: Hook)
+    where
+        Hook: Fn(Option<&Value>) -> Option<Value> + Send + Sync + 'static,
+    {
+        self.pong = Some(Arc::new(hook));
+    }
+
+    /// The payload for an outgoing ping
+    pub(super) fn ping(&self) -> Option<Value> {
+        self.ping.as_ref().map(|hook| hook())
+    }
+
+    /// The payload for a pong replying to a ping with `ping_payload`
+    pub(super) fn pong(&self, ping_payload: Option<&Value>) -> Option<Value> {
+        self.pong.as_ref().and_then(|hook| hook(ping_payload))
+    }
+}
@@ -0,0 +1,308 @@
+use std::{
+    future::Future,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use futures_lite::{FutureExt, future};
+
+use crate::{Error, graphql::GraphqlOperation, logging::trace};
+
+use super::{Client, ClientBuilder, ConnectionActor, SubscribeOptions, Subscription};
+
+type ClientFactory = Arc<dyn Fn() -> future::Boxed<Result<ClientBuilder, Error>> + Send + Sync>;
+
+type Spawner = Arc<dyn Fn(ConnectionActor) + Send + Sync>;
+
+/// A pool of clients that spreads subscriptions across several connections.
+///
+/// Connections are opened on demand, up to
+/// [`ClientPoolBuilder::max_connections`].  Each subscription is started on
+/// the connection that is running the fewest operations, opening a new
+/// connection instead if every existing one is busy and the pool has room.
+/// If the pool is full but a connection is still being opened, subscriptions
+/// wait for it to open rather than failing.
+///
+/// ```rust
+/// use graphql_ws_client::{Client, ClientPool};
+/// use std::future::IntoFuture;
+/// # use graphql_ws_client::__doc_utils::spawn;
+/// # async fn example() -> Result<(), graphql_ws_client::Error> {
+/// # let subscription = graphql_ws_client::__doc_utils::Subscription;
+///
+/// let pool = ClientPool::builder(
+///     || async {
+///         // Open a new websocket connection here
+///         # let connection = graphql_ws_client::__doc_utils::Conn;
+///         Ok(Client::build(connection))
+///     },
+///     |actor| spawn(actor.into_future()),
+/// )
+/// .max_connections(4)
+/// .max_operations_per_connection(100)
+/// .build();
+///
+/// let stream = pool.subscribe(subscription).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ClientPool {
+    inner: Arc<PoolInner>,
+}
+
+/// Builder for a [`ClientPool`]
+#[must_use]
+pub struct ClientPoolBuilder {
+    factory: ClientFactory,
+    spawner: Spawner,
+    max_connections: usize,
+    max_operations: Option<usize>,
+}
+
+struct PoolInner {
+    factory: ClientFactory,
+    spawner: Spawner,
+    max_connections: usize,
+    max_operations: Option<usize>,
+    state: Mutex<PoolState>,
+}
+
+#[derive(Default)]
+struct PoolState {
+    connections: Vec<PooledClient>,
+
+    /// A receiver for each connection that is currently being opened, which
+    /// closes once it has opened or failed to
+    opening: Vec<async_channel::Receiver<()>>,
+}
+
+struct PooledClient {
+    client: Client,
+
+    /// Subscriptions that have been placed on this client but not yet started
+    placing: Arc<AtomicUsize>,
+}
+
+impl PooledClient {
+    fn load(&self) -> usize {
+        self.client.operation_count() + self.placing.load(Ordering::Relaxed)
+    }
+}
+
+enum Placement {
+    Existing(Client, Placing),
+    Open(Opening),
+    /// Wait for a connection that is being opened, then try again
+    Wait(async_channel::Receiver<()>),
+}
+
+impl ClientPool {
+    /// Creates a `ClientPoolBuilder`.
+    ///
+    /// `factory` is called whenever the pool needs a new connection, and should
+    /// return a [`ClientBuilder`] for a freshly opened connection.  `spawner` is
+    /// given the [`ConnectionActor`] for each connection, and should spawn it on
+    /// an async runtime.
+    pub fn builder<Factory, Fut, Spawn>(factory: Factory, spawner: Spawn) -> ClientPoolBuilder
+    where
+        Factory: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<ClientBuilder, Error>> + Send + 'static,
+        Spawn: Fn(ConnectionActor) + Send + Sync + 'static,
+    {
+        ClientPoolBuilder {
+            factory: Arc::new(move || factory().boxed()),
+            spawner: Arc::new(spawner),
+            max_connections: 4,
+            max_operations: None,
+        }
+    }
+
+    /// Starts a streaming operation on the least loaded connection in the pool.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::PoolExhausted`] if every connection is at
+    /// [`ClientPoolBuilder::max_operations_per_connection`], or any error
+    /// from opening a new connection or from [`Client::subscribe`].
+    pub async fn subscribe<Operation>(
+        &self,
+        op: Operation,
+    ) -> Result<Subscription<Operation>, Error>
+    where
+        Operation: GraphqlOperation + Unpin + Send + 'static,
+    {
+        self.subscribe_with(op, SubscribeOptions::default()).await
+    }
+
+    /// Starts a streaming operation on the least loaded connection in the pool,
+    /// overriding the client's settings for this subscription.
+    ///
+    /// See [`Client::subscribe_with`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::PoolExhausted`] if every connection is at
+    /// [`ClientPoolBuilder::max_operations_per_connection`], or any error
+    /// from opening a new connection or from [`Client::subscribe_with`].
+    pub async fn subscribe_with<Operation>(
+        &self,
+        op: Operation,
+        options: SubscribeOptions,
+    ) -> Result<Subscription<Operation>, Error>
+    where
+        Operation: GraphqlOperation + Unpin + Send + 'static,
+    {
+        let (client, _placing) = loop {
+            match self.inner.place()? {
+                Placement::Existing(client, placing) => break (client, placing),
+                Placement::Open(opening) => break self.inner.open(opening).await?,
+                Placement::Wait(opened) => {
+                    opened.recv().await.ok();
+                }
+            }
+        };
+
+        client.subscribe_with(op, options).await
+    }
+
+    /// The number of connections that are currently open
+    pub fn connection_count(&self) -> usize {
+        let mut state = self.inner.state.lock().unwrap();
+        state.prune();
+        state.connections.len()
+    }
+}
+
+impl ClientPoolBuilder {
+    /// The most connections the pool will open at once.
+    ///
+    /// This defaults to 4.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is 0.
+    pub fn max_connections(self, count: usize) -> Self {
+        assert!(count > 0, "a ClientPool needs at least one connection");
+        ClientPoolBuilder {
+            max_connections: count,
+            ..self
+        }
+    }
+
+    /// The most operations the pool will run on each connection.
+    ///
+    /// Once every connection is running this many operations
+    /// [`ClientPool::subscribe`] will return [`Error::PoolExhausted`].  By
+    /// default there is no limit.
+    pub fn max_operations_per_connection(self, count: usize) -> Self {
+        ClientPoolBuilder {
+            max_operations: Some(count),
+            ..self
+        }
+    }
+
+    /// Creates the `ClientPool`.
+    ///
+    /// Connections aren't opened until they are needed by a subscription.
+    pub fn build(self) -> ClientPool {
+        ClientPool {
+            inner: Arc::new(PoolInner {
+                factory: self.factory,
+                spawner: self.spawner,
+                max_connections: self.max_connections,
+                max_operations: self.max_operations,
+                state: Mutex::default(),
+            }),
+        }
+    }
+}
+
+impl PoolInner {
+    /// Picks the connection a new subscription should be started on
+    fn place(&self) -> Result<Placement, Error> {
+        let mut state = self.state.lock().unwrap();
+        state.prune();
+
+        let least_loaded = state
+            .connections
+            .iter()
+            .filter(|connection| {
+                self.max_operations
+                    .is_none_or(|max_operations| connection.load() < max_operations)
+            })
+            .min_by_key(|connection| connection.load());
+
+        let has_room = state.connections.len() + state.opening.len() < self.max_connections;
+
+        match least_loaded {
+            Some(connection) if connection.load() == 0 || !has_room => {
+                let client = connection.client.clone();
+                let placing = Placing::new(Arc::clone(&connection.placing));
+                Ok(Placement::Existing(client, placing))
+            }
+            _ if has_room => {
+                let (opened, receiver) = async_channel::bounded(1);
+                state.opening.push(receiver);
+                Ok(Placement::Open(Opening { _opened: opened }))
+            }
+            // Any connection that is still being opened might have room once it has
+            _ => match state.opening.first() {
+                Some(opening) => Ok(Placement::Wait(opening.clone())),
+                None => Err(Error::PoolExhausted),
+            },
+        }
+    }
+
+    /// Opens a new connection and adds it to the pool
+    async fn open(&self, opening: Opening) -> Result<(Client, Placing), Error> {
+        trace!("Opening a new connection for the pool");
+
+        let (client, actor) = (self.factory)().await?.await?;
+        (self.spawner)(actor);
+
+        let placing = Arc::new(AtomicUsize::new(0));
+        self.state.lock().unwrap().connections.push(PooledClient {
+            client: client.clone(),
+            placing: Arc::clone(&placing),
+        });
+        drop(opening);
+
+        Ok((client, Placing::new(placing)))
+    }
+}
+
+impl PoolState {
+    /// Removes any connections whose actor has stopped, and any that have
+    /// finished opening
+    fn prune(&mut self) {
+        self.connections
+            .retain(|connection| !connection.client.is_closed());
+        self.opening.retain(|opening| !opening.is_closed());
+    }
+}
+
+/// Counts a subscription against a connection's load until it has started,
+/// after which the connection's own operation count includes it
+struct Placing(Arc<AtomicUsize>);
+
+impl Placing {
+    fn new(placing: Arc<AtomicUsize>) -> Self {
+        placing.fetch_add(1, Ordering::Relaxed);
+        Placing(placing)
+    }
+}
+
+impl Drop for Placing {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Counts a connection that is being opened against the pool's limit, and
+/// wakes anything waiting on it when dropped
+struct Opening {
+    _opened: async_channel::Sender<()>,
+}
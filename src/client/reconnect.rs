@@ -0,0 +1,63 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use futures_lite::{FutureExt, future};
+
+use crate::Error;
+
+use super::connection::{Connection, ObjectSafeConnection};
+
+type ConnectionFactory =
+    Arc<dyn Fn() -> future::Boxed<Result<Box<dyn ObjectSafeConnection>, Error>> + Send + Sync>;
+
+#[derive(Clone)]
+pub(super) struct ReconnectSettings {
+    /// Creates new connections.  Reconnection is disabled if this is `None`
+    factory: Option<ConnectionFactory>,
+
+    /// How many times to try connecting before giving up
+    pub(super) attempts: usize,
+
+    /// How long to wait before each connection attempt
+    pub(super) delay: Duration,
+}
+
+impl Default for ReconnectSettings {
+    fn default() -> Self {
+        Self {
+            factory: None,
+            attempts: 3,
+            delay: Duration::from_secs(1),
+        }
+    }
+}
+
+impl ReconnectSettings {
+    pub(super) fn set_factory<Factory, Fut, Conn>(&mut self, factory: Factory)
+    where
+        Factory: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Conn, Error>> + Send + 'static,
+        Conn: Connection + Send + 'static,
+    {
+        self.factory = Some(Arc::new(move || {
+            let connection = factory();
+            async move {
+                connection
+                    .await
+                    .map(|connection| Box::new(connection) as Box<dyn ObjectSafeConnection>)
+            }
+            .boxed()
+        }));
+    }
+
+    pub(super) fn enabled(&self) -> bool {
+        self.factory.is_some()
+    }
+
+    pub(super) async fn connect(&self) -> Result<Box<dyn ObjectSafeConnection>, Error> {
+        let Some(factory) = &self.factory else {
+            return Err(Error::Unknown("reconnection is not enabled".into()));
+        };
+
+        factory().await
+    }
+}
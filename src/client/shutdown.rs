@@ -0,0 +1,116 @@
+use serde_json::Value;
+
+use crate::CloseCode;
+
+use super::{CompletionReason, outcome::OutcomeReceiver};
+
+/// Why a [`ConnectionActor`](super::ConnectionActor) stopped.
+///
+/// This is the output of the actor future, and can also be retrieved from an
+/// [`ActorHandle`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShutdownReason {
+    /// Every `Client` & `Subscription` was dropped
+    ClientsDropped,
+    /// The connection was closed with [`Client::close`](super::Client::close)
+    /// or [`Client::shutdown`](super::Client::shutdown)
+    ClientClosed {
+        /// The close code that was sent to the server
+        code: CloseCode,
+        /// The reason that was sent to the server, if any
+        reason: Option<String>,
+    },
+    /// The server closed the connection
+    ServerClosed {
+        /// The close code the server sent, if any
+        code: Option<CloseCode>,
+        /// The reason the server gave, if any
+        reason: Option<String>,
+    },
+    /// The server stopped responding to keep alives
+    KeepAliveTimeout,
+    /// The connection dropped without being closed
+    ConnectionLost,
+    /// A message couldn't be sent to the server
+    SendFailed(String),
+    /// A message from the server couldn't be decoded
+    DecodeError(String),
+    /// The server sent a message that isn't valid at that point in the protocol
+    ProtocolError(String),
+    /// The server rejected the connection with a `connection_error` message,
+    /// which is only sent by the legacy `subscriptions-transport-ws` protocol
+    ServerRejected {
+        /// The payload of the `connection_error` message, if any
+        payload: Option<Value>,
+    },
+    /// The actor was dropped before the connection was finished with
+    ActorDropped,
+}
+
+impl ShutdownReason {
+    /// Whether the actor stopped because the connection failed.
+    ///
+    /// If reconnection is enabled these are the failures that the actor will
+    /// try to recover from, so seeing one here means reconnecting failed too.
+    ///
+    /// This follows the same rules as [`CompletionReason::is_transport_failure`].
+    pub fn is_transport_failure(&self) -> bool {
+        CompletionReason::from(self).is_transport_failure()
+    }
+}
+
+impl From<&ShutdownReason> for CompletionReason {
+    fn from(reason: &ShutdownReason) -> Self {
+        match reason {
+            ShutdownReason::ServerClosed { code, reason } => CompletionReason::ServerClosed {
+                code: *code,
+                reason: reason.clone(),
+            },
+            ShutdownReason::KeepAliveTimeout => CompletionReason::KeepAliveFailed,
+            ShutdownReason::ServerRejected { .. } => CompletionReason::ServerRejected,
+            ShutdownReason::ConnectionLost | ShutdownReason::SendFailed(_) => {
+                CompletionReason::ConnectionLost
+            }
+            ShutdownReason::ClientsDropped
+            | ShutdownReason::ClientClosed { .. }
+            | ShutdownReason::DecodeError(_)
+            | ShutdownReason::ProtocolError(_)
+            | ShutdownReason::ActorDropped => CompletionReason::ConnectionClosed,
+        }
+    }
+}
+
+/// A cloneable handle for finding out why a [`ConnectionActor`](super::ConnectionActor) stopped.
+///
+/// This can be created with [`ConnectionActor::handle`](super::ConnectionActor::handle)
+/// before the actor is spawned, and then passed to any task that needs to
+/// know when the connection has gone away.
+///
+/// ```rust,no_run
+/// use graphql_ws_client::Client;
+/// use std::future::IntoFuture;
+/// # use graphql_ws_client::__doc_utils::spawn;
+/// # async fn example() -> Result<(), graphql_ws_client::Error> {
+/// # let connection = graphql_ws_client::__doc_utils::Conn;
+/// let (client, actor) = Client::build(connection).await?;
+///
+/// let handle = actor.handle();
+/// spawn(actor.into_future());
+///
+/// let reason = handle.shutdown_reason().await;
+/// println!("connection shut down: {reason:?}");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ActorHandle {
+    pub(super) reason: OutcomeReceiver<ShutdownReason>,
+}
+
+impl ActorHandle {
+    /// Waits for the actor to stop, returning the reason it stopped
+    pub fn shutdown_reason(&self) -> impl Future<Output = ShutdownReason> + Send + 'static {
+        let reason = self.reason.clone();
+        async move { reason.wait().await.unwrap_or(ShutdownReason::ActorDropped) }
+    }
+}
@@ -1,6 +1,6 @@
 use std::{
     pin::Pin,
-    task::{Context, Poll},
+    task::{Context, Poll, ready},
 };
 
 use futures_lite::{Stream, StreamExt, future, stream};
@@ -9,7 +9,7 @@ use crate::{
     Error, SubscriptionId, client::production_future::read_from_producer, graphql::GraphqlOperation,
 };
 
-use super::ConnectionCommand;
+use super::{CompletionReason, ConnectionCommand, completion::CompletionReceiver};
 
 /// A `futures::Stream` for a subscription.
 ///
@@ -22,7 +22,11 @@ where
     pub(in crate::client) id: SubscriptionId,
     pub(in crate::client) stream: Option<stream::Boxed<Result<Operation::Response, Error>>>,
     pub(in crate::client) actor: async_channel::Sender<ConnectionCommand>,
-    pub(in crate::client) drop_sender: Option<async_channel::Sender<SubscriptionId>>,
+    pub(in crate::client) drop_sender: Option<async_channel::Sender<ConnectionCommand>>,
+    pub(in crate::client) completion: CompletionReceiver,
+    /// Held until the stream has been read to the end, so that
+    /// `Client::shutdown` can wait for buffered responses to be read
+    pub(in crate::client) reading: Option<async_channel::Sender<()>>,
 }
 
 #[pin_project::pinned_drop]
@@ -36,7 +40,12 @@ where
         };
         // We try_send here but the drop_sender channel _should_ be unbounded so
         // this should always work if the connection actor is still alive.
-        drop_sender.try_send(self.id).ok();
+        drop_sender
+            .try_send(ConnectionCommand::Cancel {
+                id: self.id.clone(),
+                stopped: None,
+            })
+            .ok();
     }
 }
 
@@ -50,18 +59,72 @@ where
     /// a running subscription without needing access to the `Subscription`
     /// itself.
     pub fn id(&self) -> SubscriptionId {
-        self.id
+        self.id.clone()
+    }
+
+    /// Returns a future that resolves with the reason this subscription ended.
+    ///
+    /// The future doesn't borrow the subscription, so it can be created
+    /// before the stream is consumed:
+    ///
+    /// ```rust,no_run
+    /// use futures_lite::StreamExt;
+    /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+    /// # let connection = graphql_ws_client::__doc_utils::Conn;
+    /// # let subscription = graphql_ws_client::__doc_utils::Subscription;
+    /// # let (client, _) = graphql_ws_client::Client::build(connection).await?;
+    /// let mut stream = client.subscribe(subscription).await?;
+    /// let completion = stream.completion();
+    ///
+    /// while let Some(item) = stream.next().await {
+    ///     // Do something with the item
+    /// }
+    ///
+    /// if completion.await.is_transport_failure() {
+    ///     // Resubscribe on a new connection
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn completion(&self) -> impl Future<Output = CompletionReason> + Send + 'static {
+        let completion = self.completion.clone();
+        async move {
+            // If there's no reason the actor was dropped while the operation was running
+            completion
+                .wait()
+                .await
+                .unwrap_or(CompletionReason::ConnectionClosed)
+        }
     }
 
     /// Stops this subscription
-    pub fn stop(mut self) {
+    ///
+    /// The returned future resolves once the `complete` message for this
+    /// subscription has been sent to the server.  If the future is dropped
+    /// without being awaited the subscription is still stopped, as with
+    /// dropping the `Subscription` itself.
+    pub async fn stop(mut self) {
+        // Drop our end of the stream first, so the actor can't be stuck
+        // waiting for us to read from it
+        self.stream = None;
+
         let Some(drop_sender) = self.drop_sender.take() else {
             return;
         };
 
+        let (stopped_sender, stopped) = async_channel::bounded(1);
+
         // We try_send here but the drop_sender channel _should_ be unbounded so
         // this should always work if the connection actor is still alive.
-        drop_sender.try_send(self.id).ok();
+        drop_sender
+            .try_send(ConnectionCommand::Cancel {
+                id: self.id.clone(),
+                stopped: Some(stopped_sender),
+            })
+            .ok();
+
+        // The actor drops its sender once the complete has been sent
+        stopped.recv().await.ok();
     }
 
     pub(super) fn join(mut self, future: future::Boxed<()>) -> Self
@@ -83,10 +146,16 @@ where
     type Item = Result<Operation::Response, Error>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        match self.project().stream.as_mut() {
-            None => Poll::Ready(None),
-            Some(stream) => stream.poll_next(cx),
+        let this = self.project();
+        let Some(stream) = this.stream.as_mut() else {
+            return Poll::Ready(None);
+        };
+
+        let next = ready!(stream.poll_next(cx));
+        if next.is_none() {
+            this.reading.take();
         }
+        Poll::Ready(next)
     }
 }
 
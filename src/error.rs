@@ -1,3 +1,5 @@
+use crate::{CloseCode, SubscriptionId, graphql::GraphqlError};
+
 #[derive(thiserror::Error, Debug)]
 /// Error type
 pub enum Error {
@@ -9,7 +11,7 @@ pub enum Error {
     Custom(String, String),
     /// Unexpected close frame
     #[error("got close frame. code: {0}, reason: {1}")]
-    Close(u16, String),
+    Close(CloseCode, String),
     /// Decoding / parsing error
     #[error("message decode error, reason: {0}")]
     Decode(String),
@@ -25,10 +27,48 @@ pub enum Error {
     /// Sender shutdown error
     #[error("sender shutdown error, reason: {0}")]
     SenderShutdown(String),
-    /// Too many existing connections have been created.
+    /// The server ended an operation with an `error` message.
     ///
-    /// Note that this would require a usize to be exhausted so is quite
-    /// unlikely
-    #[error("connection ID space exhausted.  please restart the client")]
-    ConnectionIdsExhausted,
+    /// This is returned from a subscription when the operation fails as a whole, for
+    /// example due to validation errors.  Errors that occur while resolving fields are
+    /// instead returned inside the response alongside any partial data.
+    #[error("operation failed: {}", format_errors(.0))]
+    Operation(Vec<GraphqlError>),
+    /// The server did not send a `connection_ack` within the time set by
+    /// `ClientBuilder::connection_init_timeout`
+    #[error("timed out waiting for connection_ack")]
+    ConnectionInitTimeout,
+    /// A subscription was started with the same id as one that is already running
+    #[error("a subscription with id {0} already exists")]
+    SubscriberAlreadyExists(SubscriptionId),
+    /// A subscription's buffer filled up while using `OverflowPolicy::Fail`
+    #[error("subscription buffer overflowed")]
+    SubscriptionOverflow,
+    /// Opening a websocket connection with `ClientBuilder::connect` failed
+    #[error("couldn't connect, reason: {0}")]
+    Connect(String),
+    /// The server didn't select a websocket subprotocol that the client can speak
+    /// when connecting with `ClientBuilder::connect`.
+    ///
+    /// This contains the subprotocols that were requested.
+    #[error("server did not select a supported websocket subprotocol, requested: {0}")]
+    SubprotocolNotSelected(String),
+    /// Every connection in a `ClientPool` is running its maximum number of operations
+    #[error("every connection in the pool is at its operation limit")]
+    PoolExhausted,
+    /// A subscription was started while the connection was being shut down
+    /// with `Client::shutdown`
+    #[error("the connection is shutting down")]
+    ShuttingDown,
+    /// An operation run with `Client::execute` completed without sending a result
+    #[error("operation completed without a response")]
+    NoResponse,
+}
+
+fn format_errors(errors: &[GraphqlError]) -> String {
+    errors
+        .iter()
+        .map(|error| error.message.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
 }
@@ -8,6 +8,88 @@
 //! [cynic]: https://cynic-rs.dev
 //! [graphql-client]: https://github.com/graphql-rust/graphql-client
 
+use serde_json::Value;
+
+/// An error returned by a GraphQL server.
+///
+/// These are received when an operation fails as a whole, for example when
+/// it does not pass validation, and are returned from a `Subscription` as an
+/// [`Error::Operation`](crate::Error::Operation).
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct GraphqlError {
+    /// A description of the error
+    pub message: String,
+
+    /// The locations in the GraphQL document that the error relates to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locations: Option<Vec<ErrorLocation>>,
+
+    /// The path of the response field that the error relates to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<Vec<PathSegment>>,
+
+    /// Any additional information the server provided about the error
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extensions: Option<Value>,
+}
+
+/// A location in a GraphQL document
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ErrorLocation {
+    /// The line number, starting from 1
+    pub line: u32,
+
+    /// The column number, starting from 1
+    pub column: u32,
+}
+
+/// A segment of the path to a response field
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum PathSegment {
+    /// The name of a field
+    Field(String),
+
+    /// An index into a list
+    Index(usize),
+}
+
+impl GraphqlError {
+    /// Reads the errors from the payload of an `error` message.
+    ///
+    /// Servers don't always send errors in the shape the spec requires, so any
+    /// error that can't be read is kept as an error whose message is the raw
+    /// JSON, rather than failing to decode the message.
+    pub(crate) fn from_payload(payload: Value) -> Vec<GraphqlError> {
+        match payload {
+            Value::Array(errors) => errors.iter().map(GraphqlError::from_value).collect(),
+            error => vec![GraphqlError::from_value(&error)],
+        }
+    }
+
+    fn from_value(error: &Value) -> GraphqlError {
+        use serde::Deserialize;
+
+        GraphqlError::deserialize(error).unwrap_or_else(|_| GraphqlError {
+            message: match error.get("message").and_then(Value::as_str) {
+                Some(message) => message.to_owned(),
+                None => error.to_string(),
+            },
+            locations: None,
+            path: None,
+            extensions: None,
+        })
+    }
+}
+
+impl std::fmt::Display for GraphqlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for GraphqlError {}
+
 /// An abstraction over GraphQL operations.
 pub trait GraphqlOperation: serde::Serialize {
     /// The actual response & error type of this operation.
@@ -40,6 +122,21 @@ mod cynic {
             serde_json::from_value(response)
         }
     }
+
+    #[cfg_attr(docsrs, doc(cfg(feature = "client-cynic")))]
+    impl<ResponseData, Variables> GraphqlOperation for ::cynic::Operation<ResponseData, Variables>
+    where
+        ResponseData: serde::de::DeserializeOwned,
+        Variables: serde::Serialize,
+    {
+        type Response = ::cynic::GraphQlResponse<ResponseData>;
+
+        type Error = serde_json::Error;
+
+        fn decode(&self, response: serde_json::Value) -> Result<Self::Response, Self::Error> {
+            serde_json::from_value(response)
+        }
+    }
 }
 
 #[cfg(feature = "client-graphql-client")]
@@ -1,8 +1,10 @@
 //! # graphql-ws-client
 //!
 //! graphql-ws-client implements asynchronous GraphQL-over-Websocket using the
-//! [graphql-transport-ws protocol][protocol].  It is websocket client, graphql
-//! client _and_ async runtime agnostic.  Built in support is provided for:
+//! [graphql-transport-ws protocol][protocol], with support for the legacy
+//! [subscriptions-transport-ws protocol][legacy-protocol] via [`Protocol`].
+//! It is websocket client, graphql client _and_ async runtime agnostic.  Built
+//! in support is provided for:
 //!
 //! - [Cynic][cynic] & [Graphql-Client][graphql-client] GraphQL clients.
 //! - [async-tungstenite][async-tungstenite], [tokio-tungstenite][tokio-tungstenite]
@@ -31,6 +33,7 @@
 //! See the [examples][examples] for more thorough usage details.
 //!
 //! [protocol]: https://github.com/graphql/graphql-over-http/blob/main/rfcs/GraphQLOverWebSocket.md
+//! [legacy-protocol]: https://github.com/apollographql/subscriptions-transport-ws/blob/master/PROTOCOL.md
 //! [cynic]: https://cynic-rs.dev
 //! [graphql-client]: https://github.com/graphql-rust/graphql-client
 //! [async-tungstenite]: https://github.com/sdroege/async-tungstenite
@@ -65,6 +68,22 @@ pub mod ws_stream_wasm;
 
 mod native;
 
+#[cfg(feature = "tokio-tungstenite-0.23")]
+pub use native::tokio_tungstenite_0_23;
+#[cfg(feature = "tokio-tungstenite-0.24")]
+pub use native::tokio_tungstenite_0_24;
+#[cfg(feature = "tokio-tungstenite-0.26")]
+pub use native::tokio_tungstenite_0_26;
+#[cfg(feature = "tokio-tungstenite-0.27")]
+pub use native::tokio_tungstenite_0_27;
+#[cfg(feature = "tokio-tungstenite-0.28")]
+pub use native::tokio_tungstenite_0_28;
+
+#[cfg(feature = "testing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+pub mod testing;
+
 pub use client::*;
 
 pub use error::Error;
+pub use protocol::{CloseCode, Protocol};
@@ -21,3 +21,52 @@ mod tungstenite_0_27;
 #[cfg(feature = "tungstenite-0.28")]
 #[cfg_attr(docsrs, doc(cfg(feature = "tungstenite-0.28")))]
 mod tungstenite_0_28;
+
+#[cfg(any(
+    feature = "tokio-tungstenite-0.23",
+    feature = "tokio-tungstenite-0.24",
+    feature = "tokio-tungstenite-0.26",
+    feature = "tokio-tungstenite-0.27",
+    feature = "tokio-tungstenite-0.28",
+))]
+mod connect;
+
+#[cfg(feature = "tokio-tungstenite-0.23")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio-tungstenite-0.23")))]
+pub mod tokio_tungstenite_0_23 {
+    //! Opening connections with [tokio-tungstenite](https://github.com/snapview/tokio-tungstenite) 0.23
+
+    super::connect::tokio_tungstenite_connect!(tokio_tungstenite_0_23, "0.23");
+}
+
+#[cfg(feature = "tokio-tungstenite-0.24")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio-tungstenite-0.24")))]
+pub mod tokio_tungstenite_0_24 {
+    //! Opening connections with [tokio-tungstenite](https://github.com/snapview/tokio-tungstenite) 0.24
+
+    super::connect::tokio_tungstenite_connect!(tokio_tungstenite_0_24, "0.24");
+}
+
+#[cfg(feature = "tokio-tungstenite-0.26")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio-tungstenite-0.26")))]
+pub mod tokio_tungstenite_0_26 {
+    //! Opening connections with [tokio-tungstenite](https://github.com/snapview/tokio-tungstenite) 0.26
+
+    super::connect::tokio_tungstenite_connect!(tokio_tungstenite_0_26, "0.26");
+}
+
+#[cfg(feature = "tokio-tungstenite-0.27")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio-tungstenite-0.27")))]
+pub mod tokio_tungstenite_0_27 {
+    //! Opening connections with [tokio-tungstenite](https://github.com/snapview/tokio-tungstenite) 0.27
+
+    super::connect::tokio_tungstenite_connect!(tokio_tungstenite_0_27, "0.27");
+}
+
+#[cfg(feature = "tokio-tungstenite-0.28")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio-tungstenite-0.28")))]
+pub mod tokio_tungstenite_0_28 {
+    //! Opening connections with [tokio-tungstenite](https://github.com/snapview/tokio-tungstenite) 0.28
+
+    super::connect::tokio_tungstenite_connect!(tokio_tungstenite_0_28, "0.28");
+}
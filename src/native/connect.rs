@@ -0,0 +1,152 @@
+use crate::Protocol;
+
+/// Finds the `Protocol` for the subprotocol a server selected in its
+/// `Sec-WebSocket-Protocol` header.
+pub(super) fn selected_protocol(subprotocol: &str) -> Option<Protocol> {
+    [
+        Protocol::GraphqlTransportWs,
+        Protocol::SubscriptionsTransportWs,
+    ]
+    .into_iter()
+    .find(|protocol| protocol.subprotocol() == subprotocol.trim())
+}
+
+/// Defines a `ClientBuilderExt` trait that opens connections with the
+/// tokio-tungstenite crate at `$tokio_tungstenite`.
+///
+/// Each supported version of tokio-tungstenite gets its own module & trait, so
+/// that enabling more than one of the features doesn't define `connect` twice.
+macro_rules! tokio_tungstenite_connect {
+    ($tokio_tungstenite:ident, $version:literal) => {
+        use std::future::Future;
+
+        use ::$tokio_tungstenite::tungstenite::{
+            self,
+            client::IntoClientRequest,
+            error::{ProtocolError, SubProtocolError},
+            http::{HeaderValue, header::SEC_WEBSOCKET_PROTOCOL},
+        };
+
+        use crate::{Client, ClientBuilder, Error, Protocol, native::connect::selected_protocol};
+
+        #[doc = concat!("Opens connections for a [`ClientBuilder`] with tokio-tungstenite ", $version, ".")]
+        ///
+        /// This needs to be imported to call its functions:
+        ///
+        /// ```rust
+        /// use graphql_ws_client::ClientBuilder;
+        #[doc = concat!("use graphql_ws_client::", stringify!($tokio_tungstenite), "::ClientBuilderExt;")]
+        /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+        ///
+        /// let (client, actor) = ClientBuilder::connect("ws://localhost:8000/graphql")
+        ///     .await?
+        ///     .await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub trait ClientBuilderExt {
+            /// Opens a websocket connection to `url` and creates a `ClientBuilder`
+            /// for it.
+            ///
+            /// This requests the `graphql-transport-ws` subprotocol, and fails with
+            /// [`Error::SubprotocolNotSelected`] if the server doesn't select it.  Use
+            /// [`ClientBuilderExt::connect_with_request`] to send extra headers.
+            ///
+            /// Connecting to `wss://` urls requires one of the TLS features of
+            /// tokio-tungstenite to be enabled.
+            ///
+            /// # Errors
+            ///
+            /// Will return `Err` if the connection can't be opened or the server
+            /// doesn't select the subprotocol.
+            fn connect(url: &str) -> impl Future<Output = Result<ClientBuilder, Error>> + Send;
+
+            /// Opens a websocket connection using `request`, and creates a
+            /// `ClientBuilder` for it.
+            ///
+            /// This can be used to send extra headers, e.g. for authentication.  If the
+            /// request has no `Sec-WebSocket-Protocol` header then the `graphql-transport-ws`
+            /// subprotocol is requested.  Otherwise the client uses whichever
+            /// [`Protocol`] matches the subprotocol the server selects, failing with
+            /// [`Error::SubprotocolNotSelected`] if it doesn't select one.
+            ///
+            /// ```rust
+            /// use graphql_ws_client::ClientBuilder;
+            #[doc = concat!("use graphql_ws_client::", stringify!($tokio_tungstenite), "::ClientBuilderExt;")]
+            #[doc = concat!("use ", stringify!($tokio_tungstenite), "::tungstenite::{client::IntoClientRequest, http::HeaderValue};")]
+            /// # async fn example() -> Result<(), graphql_ws_client::Error> {
+            ///
+            /// let mut request = "ws://localhost:8000/graphql".into_client_request().unwrap();
+            /// request
+            ///     .headers_mut()
+            ///     .insert("Authorization", HeaderValue::from_static("Bearer abc123"));
+            ///
+            /// let (client, actor) = ClientBuilder::connect_with_request(request)
+            ///     .await?
+            ///     .await?;
+            /// # Ok(())
+            /// # }
+            /// ```
+            ///
+            /// # Errors
+            ///
+            /// Will return `Err` if the connection can't be opened or the server
+            /// doesn't select a subprotocol that the client supports.
+            fn connect_with_request<Request>(
+                request: Request,
+            ) -> impl Future<Output = Result<ClientBuilder, Error>> + Send
+            where
+                Request: IntoClientRequest;
+        }
+
+        impl ClientBuilderExt for ClientBuilder {
+            fn connect(url: &str) -> impl Future<Output = Result<ClientBuilder, Error>> + Send {
+                ClientBuilder::connect_with_request(url)
+            }
+
+            fn connect_with_request<Request>(
+                request: Request,
+            ) -> impl Future<Output = Result<ClientBuilder, Error>> + Send
+            where
+                Request: IntoClientRequest,
+            {
+                let request = request.into_client_request();
+
+                async move {
+                    let mut request = request.map_err(|error| Error::Connect(error.to_string()))?;
+
+                    let requested = request
+                        .headers_mut()
+                        .entry(SEC_WEBSOCKET_PROTOCOL)
+                        .or_insert(HeaderValue::from_static(Protocol::default().subprotocol()))
+                        .to_str()
+                        .unwrap_or_default()
+                        .to_owned();
+
+                    let (connection, response) = ::$tokio_tungstenite::connect_async(request)
+                        .await
+                        .map_err(|error| match error {
+                            tungstenite::Error::Protocol(
+                                ProtocolError::SecWebSocketSubProtocolError(
+                                    SubProtocolError::NoSubProtocol
+                                    | SubProtocolError::InvalidSubProtocol,
+                                ),
+                            ) => Error::SubprotocolNotSelected(requested.clone()),
+                            error => Error::Connect(error.to_string()),
+                        })?;
+
+                    let protocol = response
+                        .headers()
+                        .get(SEC_WEBSOCKET_PROTOCOL)
+                        .and_then(|subprotocol| subprotocol.to_str().ok())
+                        .and_then(selected_protocol)
+                        .ok_or(Error::SubprotocolNotSelected(requested))?;
+
+                    Ok(Client::build(connection).protocol(protocol))
+                }
+            }
+        }
+    };
+}
+
+pub(super) use tokio_tungstenite_connect;
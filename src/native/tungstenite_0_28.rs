@@ -24,7 +24,7 @@ where
                 Ok(tungstenite::Message::Pong(_)) => return Some(crate::client::Message::Pong),
                 Ok(tungstenite::Message::Close(frame)) => {
                     return Some(crate::client::Message::Close {
-                        code: frame.as_ref().map(|frame| frame.code.into()),
+                        code: frame.as_ref().map(|frame| u16::from(frame.code).into()),
                         reason: frame.map(|frame| frame.reason.to_string()),
                     });
                 }
@@ -46,7 +46,7 @@ where
                 crate::client::Message::Text(text) => tungstenite::Message::Text(text.into()),
                 crate::client::Message::Close { code, reason } => {
                     tungstenite::Message::Close(code.zip(reason).map(|(code, reason)| CloseFrame {
-                        code: code.into(),
+                        code: u16::from(code).into(),
                         reason: reason.into(),
                     }))
                 }
@@ -1,6 +1,255 @@
 //! Message definitions for the [graphql-transport-ws protocol][1]
 //!
 //! [1]: https://github.com/enisdenjo/graphql-ws/blob/HEAD/PROTOCOL.md
+//!
+//! The legacy protocol is defined in the `legacy` module, and [`Protocol`]
+//! handles choosing between the two.
+
+use crate::{Error, client, graphql::GraphqlError};
+
+mod legacy;
+
+/// The GraphQL over websocket protocols that a client can speak.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Protocol {
+    /// The [graphql-transport-ws protocol][1], which uses the
+    /// `graphql-transport-ws` websocket subprotocol.
+    ///
+    /// This is the default.
+    ///
+    /// [1]: https://github.com/enisdenjo/graphql-ws/blob/HEAD/PROTOCOL.md
+    #[default]
+    GraphqlTransportWs,
+
+    /// The legacy [subscriptions-transport-ws protocol][1], which uses the
+    /// `graphql-ws` websocket subprotocol.
+    ///
+    /// This protocol has no client initiated pings, so keep alives rely on the
+    /// `ka` messages sent by the server instead.
+    ///
+    /// [1]: https://github.com/apollographql/subscriptions-transport-ws/blob/master/PROTOCOL.md
+    SubscriptionsTransportWs,
+}
+
+impl Protocol {
+    /// The name of the websocket subprotocol for this protocol.
+    ///
+    /// This should be sent in the `Sec-WebSocket-Protocol` header when
+    /// opening a connection.
+    pub fn subprotocol(self) -> &'static str {
+        match self {
+            Protocol::GraphqlTransportWs => "graphql-transport-ws",
+            Protocol::SubscriptionsTransportWs => "graphql-ws",
+        }
+    }
+
+    pub(crate) fn subscribe<Operation>(
+        self,
+        id: String,
+        payload: &Operation,
+    ) -> Result<String, Error>
+    where
+        Operation: serde::Serialize,
+    {
+        match self {
+            Protocol::GraphqlTransportWs => {
+                serde_json::to_string(&Message::Subscribe { id, payload })
+            }
+            Protocol::SubscriptionsTransportWs => {
+                serde_json::to_string(&legacy::Message::Start { id, payload })
+            }
+        }
+        .map_err(|error| Error::Serializing(error.to_string()))
+    }
+
+    pub(crate) fn complete(self, id: String) -> client::Message {
+        client::Message::Text(
+            match self {
+                Protocol::GraphqlTransportWs => {
+                    serde_json::to_string(&Message::Complete::<()> { id })
+                }
+                Protocol::SubscriptionsTransportWs => {
+                    serde_json::to_string(&legacy::Message::Stop::<()> { id })
+                }
+            }
+            .unwrap(),
+        )
+    }
+
+    pub(crate) fn ping(self, payload: Option<serde_json::Value>) -> Option<client::Message> {
+        match self {
+            Protocol::GraphqlTransportWs => Some(client::Message::Text(
+                serde_json::to_string(&Message::Ping::<()> { payload }).unwrap(),
+            )),
+            Protocol::SubscriptionsTransportWs => None,
+        }
+    }
+
+    pub(crate) fn pong(self, payload: Option<serde_json::Value>) -> Option<client::Message> {
+        match self {
+            Protocol::GraphqlTransportWs => Some(client::Message::Text(
+                serde_json::to_string(&Message::Pong::<()> { payload }).unwrap(),
+            )),
+            Protocol::SubscriptionsTransportWs => None,
+        }
+    }
+
+    /// The message to send before a client closes the connection, if any
+    pub(crate) fn terminate(self) -> Option<client::Message> {
+        match self {
+            Protocol::GraphqlTransportWs => None,
+            Protocol::SubscriptionsTransportWs => Some(client::Message::Text(
+                serde_json::to_string(&legacy::Message::ConnectionTerminate::<()>).unwrap(),
+            )),
+        }
+    }
+
+    pub(crate) fn decode(self, text: &str) -> Result<Event, Error> {
+        match self {
+            Protocol::GraphqlTransportWs => {
+                serde_json::from_str(text).map_err(|error| Error::Decode(error.to_string()))
+            }
+            Protocol::SubscriptionsTransportWs => serde_json::from_str::<legacy::Event>(text)
+                .map_err(|error| Error::Decode(error.to_string()))?
+                .into_event(),
+        }
+    }
+}
+
+/// The code sent when a websocket connection is closed.
+///
+/// This covers the codes defined by the [graphql-transport-ws protocol][1],
+/// the codes this library uses when it closes a connection, and the few
+/// websocket codes that are relevant.  Any other code is represented by
+/// [`CloseCode::Other`].
+///
+/// Codes are compared by their numeric value, so `CloseCode::Other(1000)` is
+/// equal to `CloseCode::Normal`.
+///
+/// [1]: https://github.com/enisdenjo/graphql-ws/blob/HEAD/PROTOCOL.md
+#[derive(Clone, Copy, Debug)]
+pub enum CloseCode {
+    /// 1000: The connection was closed normally
+    Normal,
+    /// 1001: The server is going away, e.g. shutting down for a deploy
+    GoingAway,
+    /// 1005: The close frame did not contain a code
+    NoStatusReceived,
+    /// 4400: The server received a message it couldn't handle
+    BadRequest,
+    /// 4401: The connection has not been acknowledged yet
+    Unauthorized,
+    /// 4403: The server rejected the `connection_init` payload
+    Forbidden,
+    /// 4408: No `connection_ack` was received in time
+    ConnectionInitialisationTimeout,
+    /// 4409: An operation with the same id is already running
+    SubscriberAlreadyExists,
+    /// 4429: More than one `connection_init` was sent
+    TooManyInitialisationRequests,
+    /// 4499: The connection was terminated
+    Terminated,
+    /// 4500: The server had an internal error
+    InternalServerError,
+    /// 100: Sent by this library when every `Client` has been dropped
+    ClientsDropped,
+    /// 4503: Sent by this library when the server stops responding to keep alives
+    KeepAliveFailure,
+    /// 4855: Sent by this library when the server sends more than one `connection_ack`
+    TooManyAcknowledges,
+    /// 4856: Previously sent by this library when the server sent an id that
+    /// could not be parsed.  Any id is now accepted.
+    UnknownSubscription,
+    /// 4857: Sent by this library when a message from the server could not be decoded
+    DecodeError,
+    /// 4950: Sent by this library when the server sends something other than a
+    /// `connection_ack` during the handshake
+    UnexpectedMessage,
+    /// Any other close code
+    Other(u16),
+}
+
+impl From<u16> for CloseCode {
+    fn from(code: u16) -> Self {
+        match code {
+            1000 => CloseCode::Normal,
+            1001 => CloseCode::GoingAway,
+            1005 => CloseCode::NoStatusReceived,
+            4400 => CloseCode::BadRequest,
+            4401 => CloseCode::Unauthorized,
+            4403 => CloseCode::Forbidden,
+            4408 => CloseCode::ConnectionInitialisationTimeout,
+            4409 => CloseCode::SubscriberAlreadyExists,
+            4429 => CloseCode::TooManyInitialisationRequests,
+            4499 => CloseCode::Terminated,
+            4500 => CloseCode::InternalServerError,
+            100 => CloseCode::ClientsDropped,
+            4503 => CloseCode::KeepAliveFailure,
+            4855 => CloseCode::TooManyAcknowledges,
+            4856 => CloseCode::UnknownSubscription,
+            4857 => CloseCode::DecodeError,
+            4950 => CloseCode::UnexpectedMessage,
+            other => CloseCode::Other(other),
+        }
+    }
+}
+
+impl From<CloseCode> for u16 {
+    fn from(code: CloseCode) -> Self {
+        match code {
+            CloseCode::Normal => 1000,
+            CloseCode::GoingAway => 1001,
+            CloseCode::NoStatusReceived => 1005,
+            CloseCode::BadRequest => 4400,
+            CloseCode::Unauthorized => 4401,
+            CloseCode::Forbidden => 4403,
+            CloseCode::ConnectionInitialisationTimeout => 4408,
+            CloseCode::SubscriberAlreadyExists => 4409,
+            CloseCode::TooManyInitialisationRequests => 4429,
+            CloseCode::Terminated => 4499,
+            CloseCode::InternalServerError => 4500,
+            CloseCode::ClientsDropped => 100,
+            CloseCode::KeepAliveFailure => 4503,
+            CloseCode::TooManyAcknowledges => 4855,
+            CloseCode::UnknownSubscription => 4856,
+            CloseCode::DecodeError => 4857,
+            CloseCode::UnexpectedMessage => 4950,
+            CloseCode::Other(code) => code,
+        }
+    }
+}
+
+impl CloseCode {
+    /// Whether this code means the server rejected the client, e.g. because
+    /// authentication failed, so connecting again would fail the same way.
+    ///
+    /// This is any 44xx code apart from 4408 & 4499, which a server sends when
+    /// the client was too slow to initialise or when it is going away, and so
+    /// may well accept the client next time.
+    pub(crate) fn is_rejection(self) -> bool {
+        matches!(u16::from(self), 4400..=4407 | 4409..=4498)
+    }
+}
+
+impl PartialEq for CloseCode {
+    fn eq(&self, other: &Self) -> bool {
+        u16::from(*self) == u16::from(*other)
+    }
+}
+
+impl Eq for CloseCode {}
+
+impl std::hash::Hash for CloseCode {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        u16::from(*self).hash(state);
+    }
+}
+
+impl std::fmt::Display for CloseCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", u16::from(*self))
+    }
+}
 
 #[derive(Default, Debug)]
 pub struct ConnectionInit<Payload = ()> {
@@ -40,9 +289,15 @@ pub enum Message<'a, Operation> {
     #[serde(rename = "complete")]
     Complete { id: String },
     #[serde(rename = "ping")]
-    Ping,
+    Ping {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        payload: Option<serde_json::Value>,
+    },
     #[serde(rename = "pong")]
-    Pong,
+    Pong {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        payload: Option<serde_json::Value>,
+    },
 }
 
 #[allow(dead_code)]
@@ -57,7 +312,8 @@ pub enum Event {
     #[serde(rename = "error")]
     Error {
         id: String,
-        payload: Vec<serde_json::Value>,
+        #[serde(deserialize_with = "deserialize_errors")]
+        payload: Vec<GraphqlError>,
     },
     #[serde(rename = "complete")]
     Complete { id: String },
@@ -67,18 +323,21 @@ pub enum Event {
     Ping { payload: Option<serde_json::Value> },
     #[serde(rename = "pong")]
     Pong { payload: Option<serde_json::Value> },
+    /// Sent by `subscriptions-transport-ws` servers that reject the connection
+    #[serde(skip_deserializing)]
+    ConnectionError { payload: Option<serde_json::Value> },
 }
 
-impl Event {
-    pub fn id(&self) -> Option<&str> {
-        match self {
-            Event::Next { id, .. } | Event::Complete { id, .. } | Event::Error { id, .. } => {
-                Some(id.as_ref())
-            }
-            Event::Ping { .. } | Event::Pong { .. } | Event::ConnectionAck { .. } => None,
-        }
-    }
+fn deserialize_errors<'de, D>(deserializer: D) -> Result<Vec<GraphqlError>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::Deserialize;
+
+    serde_json::Value::deserialize(deserializer).map(GraphqlError::from_payload)
+}
 
+impl Event {
     pub fn r#type(&self) -> &'static str {
         match self {
             Event::Next { .. } => "next",
@@ -87,6 +346,7 @@ impl Event {
             Event::Ping { .. } => "ping",
             Event::Pong { .. } => "pong",
             Event::ConnectionAck { .. } => "connection_ack",
+            Event::ConnectionError { .. } => "connection_error",
         }
     }
 }
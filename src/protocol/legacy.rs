@@ -0,0 +1,53 @@
+//! Message definitions for the legacy [subscriptions-transport-ws protocol][1]
+//!
+//! [1]: https://github.com/apollographql/subscriptions-transport-ws/blob/master/PROTOCOL.md
+
+use serde_json::Value;
+
+use crate::{Error, graphql::GraphqlError};
+
+#[derive(serde::Serialize)]
+#[serde(tag = "type")]
+pub enum Message<'a, Operation> {
+    #[serde(rename = "start")]
+    Start { id: String, payload: &'a Operation },
+    #[serde(rename = "stop")]
+    Stop { id: String },
+    #[serde(rename = "connection_terminate")]
+    ConnectionTerminate,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(tag = "type")]
+pub enum Event {
+    #[serde(rename = "connection_ack")]
+    ConnectionAck { payload: Option<Value> },
+    #[serde(rename = "connection_error")]
+    ConnectionError { payload: Option<Value> },
+    #[serde(rename = "ka")]
+    KeepAlive {},
+    #[serde(rename = "data")]
+    Data { id: String, payload: Value },
+    #[serde(rename = "error")]
+    Error { id: String, payload: Value },
+    #[serde(rename = "complete")]
+    Complete { id: String },
+}
+
+impl Event {
+    /// Converts this event into its graphql-transport-ws equivalent
+    pub fn into_event(self) -> Result<super::Event, Error> {
+        Ok(match self {
+            Event::ConnectionAck { payload } => super::Event::ConnectionAck { payload },
+            Event::ConnectionError { payload } => super::Event::ConnectionError { payload },
+            // Keep alives serve the same purpose as a pong would in graphql-transport-ws
+            Event::KeepAlive {} => super::Event::Pong { payload: None },
+            Event::Data { id, payload } => super::Event::Next { id, payload },
+            Event::Error { id, payload } => super::Event::Error {
+                id,
+                payload: GraphqlError::from_payload(payload),
+            },
+            Event::Complete { id } => super::Event::Complete { id },
+        })
+    }
+}
@@ -0,0 +1,248 @@
+//! Utilities for testing code that uses `graphql-ws-client` without opening a
+//! real websocket.
+//!
+//! [`connection_pair`] returns an [`InMemoryConnection`] that can be passed to
+//! [`Client::build`](crate::Client::build), and a [`FakeServer`] that is used to
+//! script the other end of the connection.
+//!
+//! ```rust
+//! use graphql_ws_client::{Client, testing::connection_pair};
+//! use futures_lite::StreamExt;
+//! use std::future::IntoFuture;
+//! use serde_json::json;
+//! # async fn example() -> Result<(), graphql_ws_client::Error> {
+//! # let subscription = graphql_ws_client::__doc_utils::Subscription;
+//! let (connection, mut server) = connection_pair();
+//! server.ack();
+//!
+//! let (client, actor) = Client::build(connection).await?;
+//! # graphql_ws_client::__doc_utils::spawn(actor.into_future());
+//! let mut stream = client.subscribe(subscription).await?;
+//!
+//! let subscribe = server.receive_subscribe().await;
+//! assert_eq!(subscribe.payload["query"], "subscription { books { id } }");
+//!
+//! server.next(&subscribe.id, json!({"data": {"books": {"id": "123"}}}));
+//! server.complete(&subscribe.id);
+//!
+//! while let Some(response) = stream.next().await {
+//!     // Check the response
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The helpers on [`FakeServer`] speak the `graphql-transport-ws` protocol.
+//! [`FakeServer::send`] & [`FakeServer::receive`] can be used to exchange any
+//! other messages.
+
+use async_channel::{Receiver, Sender};
+use serde_json::{Value, json};
+
+use crate::{CloseCode, Connection, Error, Message};
+
+/// Creates a connected [`InMemoryConnection`] & [`FakeServer`]
+pub fn connection_pair() -> (InMemoryConnection, FakeServer) {
+    let (to_client, incoming) = async_channel::unbounded();
+    let (outgoing, from_client) = async_channel::unbounded();
+
+    (
+        InMemoryConnection { incoming, outgoing },
+        FakeServer {
+            to_client,
+            from_client,
+        },
+    )
+}
+
+/// A [`Connection`] that exchanges messages with a [`FakeServer`] in memory.
+///
+/// The connection is closed once the `FakeServer` is dropped.
+pub struct InMemoryConnection {
+    incoming: Receiver<Message>,
+    outgoing: Sender<Message>,
+}
+
+impl Connection for InMemoryConnection {
+    async fn receive(&mut self) -> Option<Message> {
+        self.incoming.recv().await.ok()
+    }
+
+    async fn send(&mut self, message: Message) -> Result<(), Error> {
+        self.outgoing
+            .send(message)
+            .await
+            .map_err(|error| Error::Send(error.to_string()))
+    }
+}
+
+/// The server end of an [`InMemoryConnection`].
+///
+/// Messages sent with this are queued until the client reads them, so a test can
+/// script a whole exchange up front.  The methods that receive messages panic
+/// if the client disconnects or sends something unexpected, so they can be used
+/// as assertions.
+pub struct FakeServer {
+    to_client: Sender<Message>,
+    from_client: Receiver<Message>,
+}
+
+/// A `subscribe` message received by a [`FakeServer`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubscribeMessage {
+    /// The id of the subscription
+    pub id: String,
+    /// The payload of the message, containing the `query`, `variables` etc.
+    pub payload: Value,
+}
+
+impl FakeServer {
+    /// Sends a text message containing `message` to the client
+    pub fn send(&self, message: Value) {
+        self.send_message(Message::Text(message.to_string()));
+    }
+
+    /// Sends a `connection_ack` to the client
+    pub fn ack(&self) {
+        self.send(json!({"type": "connection_ack"}));
+    }
+
+    /// Sends a `next` message with `payload` for the subscription `id`
+    pub fn next(&self, id: &str, payload: Value) {
+        self.send(json!({"type": "next", "id": id, "payload": payload}));
+    }
+
+    /// Sends an `error` message with `errors` for the subscription `id`
+    ///
+    /// `errors` should be a list of GraphQL errors.
+    pub fn error(&self, id: &str, errors: Value) {
+        self.send(json!({"type": "error", "id": id, "payload": errors}));
+    }
+
+    /// Sends a `complete` message for the subscription `id`
+    pub fn complete(&self, id: &str) {
+        self.send(json!({"type": "complete", "id": id}));
+    }
+
+    /// Closes the connection with `code` & `reason`
+    pub fn close(&self, code: CloseCode, reason: &str) {
+        self.send_message(Message::Close {
+            code: Some(code),
+            reason: Some(reason.into()),
+        });
+    }
+
+    fn send_message(&self, message: Message) {
+        // This only fails if the client has gone away, in which case the message
+        // wouldn't have been read anyway
+        self.to_client.try_send(message).ok();
+    }
+
+    /// Receives the next text message sent by the client, decoded as JSON.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the client closes the connection or sends invalid JSON.
+    pub async fn receive(&mut self) -> Value {
+        loop {
+            match self.from_client.recv().await.expect("client disconnected") {
+                Message::Text(text) => return decode(&text),
+                Message::Close { code, reason } => {
+                    panic!("client closed connection: {code:?} {reason:?}")
+                }
+                Message::Ping | Message::Pong => {}
+            }
+        }
+    }
+
+    /// Returns the next text message the client has already sent, if any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the client has closed the connection or sent invalid JSON.
+    pub fn try_receive(&mut self) -> Option<Value> {
+        loop {
+            match self.from_client.try_recv().ok()? {
+                Message::Text(text) => return Some(decode(&text)),
+                Message::Close { code, reason } => {
+                    panic!("client closed connection: {code:?} {reason:?}")
+                }
+                Message::Ping | Message::Pong => {}
+            }
+        }
+    }
+
+    /// Waits for the client to send `connection_init`, returning its payload.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the client sends any other message first, or closes the connection.
+    pub async fn receive_init(&mut self) -> Option<Value> {
+        let message = self.receive().await;
+        assert_eq!(
+            message["type"], "connection_init",
+            "expected a connection_init message, got {message}"
+        );
+        message.get("payload").cloned()
+    }
+
+    /// Waits for the client to send a `subscribe` message, skipping any
+    /// `connection_init`, `ping` or `pong` messages sent before it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the client sends any other message first, or closes the connection.
+    pub async fn receive_subscribe(&mut self) -> SubscribeMessage {
+        loop {
+            let message = self.receive().await;
+            match message["type"].as_str() {
+                Some("connection_init" | "ping" | "pong") => {}
+                Some("subscribe") => {
+                    return SubscribeMessage {
+                        id: message["id"]
+                            .as_str()
+                            .expect("subscribe message without an id")
+                            .to_owned(),
+                        payload: message["payload"].clone(),
+                    };
+                }
+                _ => panic!("expected a subscribe message, got {message}"),
+            }
+        }
+    }
+
+    /// Waits for the client to send a `subscribe` message, asserts that its payload
+    /// is `payload` and returns the id of the subscription.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the payload doesn't match, or under the same conditions as
+    /// [`FakeServer::receive_subscribe`].
+    pub async fn expect_subscribe(&mut self, payload: Value) -> String {
+        let subscribe = self.receive_subscribe().await;
+        assert_eq!(
+            subscribe.payload, payload,
+            "unexpected payload for subscription {}",
+            subscribe.id
+        );
+        subscribe.id
+    }
+
+    /// Waits for the client to close the connection, returning the close code.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the client disconnects without sending a close message.
+    pub async fn receive_close(&mut self) -> Option<CloseCode> {
+        loop {
+            match self.from_client.recv().await.expect("client disconnected") {
+                Message::Close { code, .. } => return code,
+                Message::Text(_) | Message::Ping | Message::Pong => {}
+            }
+        }
+    }
+}
+
+fn decode(text: &str) -> Value {
+    serde_json::from_str(text).expect("client sent invalid JSON")
+}
@@ -38,7 +38,7 @@ impl crate::client::Connection for Connection {
             match self.next().await? {
                 EventOrMessage::Event(WsEvent::Closed(close)) => {
                     return Some(Message::Close {
-                        code: Some(close.code),
+                        code: Some(close.code.into()),
                         reason: Some(close.reason),
                     });
                 }
@@ -63,8 +63,8 @@ impl crate::client::Connection for Connection {
         match message {
             Message::Text(text) => self.messages.send(WsMessage::Text(text)).await,
             Message::Close { code, reason } => match (code, reason) {
-                (Some(code), Some(reason)) => self.meta.close_reason(code, reason).await,
-                (Some(code), _) => self.meta.close_code(code).await,
+                (Some(code), Some(reason)) => self.meta.close_reason(code.into(), reason).await,
+                (Some(code), _) => self.meta.close_code(code.into()).await,
                 _ => self.meta.close().await,
             }
             .map(|_| ()),
@@ -0,0 +1,1290 @@
+use std::{
+    future::IntoFuture,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use assert_matches::assert_matches;
+use futures_lite::StreamExt;
+use graphql_ws_client::{
+    Client, CloseCode, CompletionReason, ConnectionEvent, Error, MalformedMessagePolicy,
+    OverflowPolicy, PersistedQueries, ShutdownReason, SubscribeOptions,
+    graphql::{ErrorLocation, GraphqlOperation, PathSegment},
+    testing::connection_pair,
+};
+use raw_operation::RawOperation;
+use serde_json::{Value, json};
+
+mod raw_operation;
+
+#[tokio::test]
+async fn test_operation_errors_end_the_subscription() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let mut stream = client
+        .subscribe(RawOperation {
+            query: "subscription { books { id } }",
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+    let id = server.receive().await["id"].clone();
+
+    server.send(json!({
+        "type": "next",
+        "id": id,
+        "payload": {
+            "data": null,
+            "errors": [{"message": "partial failure", "path": ["books", 0]}]
+        }
+    }));
+    server.send(json!({
+        "type": "error",
+        "id": id,
+        "payload": [{
+            "message": "Unknown field \"books\"",
+            "locations": [{"line": 1, "column": 16}],
+            "extensions": {"code": "GRAPHQL_VALIDATION_FAILED"}
+        }]
+    }));
+
+    // Errors in next payloads are just passed along with the response
+    let response = stream.next().await.unwrap().unwrap();
+    assert_eq!(response["errors"][0]["message"], "partial failure");
+
+    let error = stream.next().await.unwrap().unwrap_err();
+    let Error::Operation(errors) = error else {
+        panic!("expected an operation error, got {error:?}")
+    };
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "Unknown field \"books\"");
+    assert_eq!(
+        errors[0].locations,
+        Some(vec![ErrorLocation {
+            line: 1,
+            column: 16
+        }])
+    );
+    assert_matches!(errors[0].path, None);
+    assert_eq!(
+        errors[0].extensions,
+        Some(json!({"code": "GRAPHQL_VALIDATION_FAILED"}))
+    );
+
+    assert!(stream.next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_malformed_operation_errors() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let operation = RawOperation {
+        query: "subscription { books { id } }",
+    };
+    let mut first = client.subscribe(operation.clone()).await.unwrap();
+    let mut second = client.subscribe(operation).await.unwrap();
+
+    let first_id = server.receive_subscribe().await.id;
+    let second_id = server.receive_subscribe().await.id;
+
+    server.error(
+        &first_id,
+        json!([{"code": 500}, {"message": "broken", "path": {}}]),
+    );
+    server.error(&second_id, json!("something went wrong"));
+
+    let Some(Err(Error::Operation(errors))) = first.next().await else {
+        panic!("expected an operation error")
+    };
+    assert_eq!(errors[0].message, r#"{"code":500}"#);
+    assert_eq!(errors[1].message, "broken");
+
+    // The connection should still be up for other operations
+    let Some(Err(Error::Operation(errors))) = second.next().await else {
+        panic!("expected an operation error")
+    };
+    assert_eq!(errors[0].message, r#""something went wrong""#);
+}
+
+#[tokio::test]
+async fn test_connection_events() {
+    let (connection, mut server) = connection_pair();
+
+    server.send(json!({"type": "connection_ack", "payload": {"session": "abc"}}));
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    assert_eq!(client.ack_payload(), Some(json!({"session": "abc"})));
+
+    let mut events = client.events();
+
+    assert_eq!(
+        events.next().await.unwrap(),
+        ConnectionEvent::Connected {
+            payload: Some(json!({"session": "abc"}))
+        }
+    );
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+
+    server.send(json!({"type": "ping", "payload": {"sent": 1}}));
+    assert_eq!(
+        events.next().await.unwrap(),
+        ConnectionEvent::Ping {
+            payload: Some(json!({"sent": 1}))
+        }
+    );
+    assert_eq!(server.receive().await["type"], "pong");
+
+    server.close(CloseCode::BadRequest, "bad request");
+    assert_eq!(
+        events.next().await.unwrap(),
+        ConnectionEvent::ServerClosed {
+            code: Some(CloseCode::BadRequest),
+            reason: Some("bad request".into())
+        }
+    );
+    assert_eq!(events.next().await.unwrap(), ConnectionEvent::Shutdown);
+    assert!(events.next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_slow_event_listeners_drop_oldest_events() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let mut events = client.events();
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+
+    for sent in 0..40 {
+        server.send(json!({"type": "ping", "payload": {"sent": sent}}));
+    }
+    for _ in 0..40 {
+        assert_eq!(server.receive().await["type"], "pong");
+    }
+
+    // Only the most recent 32 events should have been kept
+    assert_eq!(
+        events.next().await.unwrap(),
+        ConnectionEvent::Ping {
+            payload: Some(json!({"sent": 8}))
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_ping_payloads() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection)
+        .keep_alive_interval(Duration::from_millis(20))
+        .ping_payload(|| json!({"region": "eu"}))
+        .pong_payload(|ping_payload| ping_payload.cloned())
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let mut events = client.events();
+    assert_matches!(events.next().await, Some(ConnectionEvent::Connected { .. }));
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+
+    // Pongs should be computed from the servers ping
+    server.send(json!({"type": "ping", "payload": {"sent": 1}}));
+    assert_eq!(
+        server.receive().await,
+        json!({"type": "pong", "payload": {"sent": 1}})
+    );
+
+    // Keep alive pings should use the ping payload
+    assert_eq!(
+        server.receive().await,
+        json!({"type": "ping", "payload": {"region": "eu"}})
+    );
+    server.send(json!({"type": "pong"}));
+
+    loop {
+        match events.next().await.unwrap() {
+            ConnectionEvent::Pong {
+                payload,
+                round_trip_time,
+            } => {
+                assert_eq!(payload, None);
+                assert!(round_trip_time.is_some());
+                break;
+            }
+            ConnectionEvent::Ping { .. } => {}
+            other => panic!("unexpected event: {other:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_overflow_drop_oldest() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection)
+        .subscription_buffer_size(1)
+        .overflow_policy(OverflowPolicy::DropOldest)
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let mut stream = client
+        .subscribe(RawOperation {
+            query: "subscription { books { id } }",
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+    let id = server.receive().await["id"].clone();
+
+    for n in 1..=3 {
+        server.send(json!({"type": "next", "id": id, "payload": {"data": n}}));
+    }
+
+    // The actor shouldn't be stuck waiting on the full subscription
+    server.send(json!({"type": "ping"}));
+    assert_eq!(server.receive().await["type"], "pong");
+
+    assert_eq!(stream.next().await.unwrap().unwrap()["data"], 3);
+}
+
+#[tokio::test]
+async fn test_overflow_fail() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection)
+        .subscription_buffer_size(1)
+        .overflow_policy(OverflowPolicy::Fail)
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let mut stream = client
+        .subscribe(RawOperation {
+            query: "subscription { books { id } }",
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+    let id = server.receive().await["id"].clone();
+
+    server.send(json!({"type": "next", "id": id, "payload": {"data": 1}}));
+    server.send(json!({"type": "next", "id": id, "payload": {"data": 2}}));
+
+    assert_eq!(
+        server.receive().await,
+        json!({"type": "complete", "id": id})
+    );
+
+    assert_matches!(stream.next().await, Some(Err(Error::SubscriptionOverflow)));
+    assert_matches!(stream.next().await, None);
+}
+
+#[tokio::test]
+async fn test_subscribe_with_options() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let mut stream = client
+        .subscribe_with(
+            RawOperation {
+                query: "subscription { books { id } }",
+            },
+            SubscribeOptions::default()
+                .buffer_size(1)
+                .overflow_policy(OverflowPolicy::DropNewest)
+                .id("42"),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+    assert_eq!(server.receive().await["id"], "42");
+
+    for n in 1..=3 {
+        server.send(json!({"type": "next", "id": "42", "payload": {"data": n}}));
+    }
+    server.send(json!({"type": "complete", "id": "42"}));
+
+    server.send(json!({"type": "ping"}));
+    assert_eq!(server.receive().await["type"], "pong");
+
+    assert_eq!(stream.next().await.unwrap().unwrap()["data"], 1);
+    assert_matches!(stream.next().await, None);
+}
+
+#[test]
+#[should_panic(expected = "at least one message")]
+fn test_zero_buffer_size_is_rejected() {
+    let _ = SubscribeOptions::default().buffer_size(0);
+}
+
+#[tokio::test]
+async fn test_subscription_id_generator() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let next_id = AtomicUsize::new(1);
+    let (client, actor) = Client::build(connection)
+        .subscription_id_generator(move || {
+            format!("feed-{}", next_id.fetch_add(1, Ordering::Relaxed))
+        })
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let mut stream = client
+        .subscribe(RawOperation {
+            query: "subscription { books { id } }",
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(stream.id().as_str(), "feed-1");
+    assert_eq!(server.receive().await["type"], "connection_init");
+    assert_eq!(server.receive().await["id"], "feed-1");
+
+    // Messages for ids we don't know about should be ignored
+    server.send(json!({"type": "next", "id": "a3f2-unknown", "payload": {"data": 0}}));
+    server.send(json!({"type": "next", "id": "feed-1", "payload": {"data": 1}}));
+
+    assert_eq!(stream.next().await.unwrap().unwrap()["data"], 1);
+
+    client.stop(stream.id()).await.unwrap();
+    assert_eq!(
+        server.receive().await,
+        json!({"type": "complete", "id": "feed-1"})
+    );
+}
+
+#[tokio::test]
+async fn test_duplicate_subscription_ids() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let operation = RawOperation {
+        query: "subscription { books { id } }",
+    };
+
+    let mut stream = client
+        .subscribe_with(operation.clone(), SubscribeOptions::default().id("books"))
+        .await
+        .unwrap();
+
+    let result = client
+        .subscribe_with(operation, SubscribeOptions::default().id("books"))
+        .await;
+    assert_matches!(result.err(), Some(Error::SubscriberAlreadyExists(id)) if id.as_str() == "books");
+
+    // The actor should still be running the original subscription
+    assert_eq!(server.receive().await["type"], "connection_init");
+    assert_eq!(server.receive().await["id"], "books");
+
+    server.send(json!({"type": "next", "id": "books", "payload": {"data": 1}}));
+    assert_eq!(stream.next().await.unwrap().unwrap()["data"], 1);
+}
+
+#[tokio::test]
+async fn test_generated_ids_skip_explicit_ids() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let operation = RawOperation {
+        query: "subscription { books { id } }",
+    };
+
+    let explicit = client
+        .subscribe_with(operation.clone(), SubscribeOptions::default().id("1"))
+        .await
+        .unwrap();
+    let generated = client.subscribe(operation).await.unwrap();
+
+    assert_eq!(explicit.id().as_str(), "1");
+    assert_eq!(generated.id().as_str(), "2");
+    assert_eq!(server.receive_subscribe().await.id, "1");
+    assert_eq!(server.receive_subscribe().await.id, "2");
+}
+
+#[tokio::test]
+async fn test_execute_returns_after_first_result() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let execute = tokio::spawn(async move {
+        client
+            .execute(RawOperation {
+                query: "query { books { id } }",
+            })
+            .await
+    });
+
+    let subscribe = server.receive_subscribe().await;
+
+    // The server never completes the operation, so the client has to
+    server.next(&subscribe.id, json!({"data": {"books": []}}));
+
+    let response = execute.await.unwrap().unwrap();
+    assert_eq!(response["data"], json!({"books": []}));
+    assert_eq!(
+        server.receive().await,
+        json!({"type": "complete", "id": subscribe.id})
+    );
+}
+
+#[tokio::test]
+async fn test_execute_without_a_result() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let execute = tokio::spawn(async move {
+        client
+            .execute(RawOperation {
+                query: "mutation { deleteBooks }",
+            })
+            .await
+    });
+
+    let subscribe = server.receive_subscribe().await;
+    server.complete(&subscribe.id);
+
+    let error = execute.await.unwrap().unwrap_err();
+    assert_matches!(error, Error::NoResponse);
+}
+
+#[tokio::test]
+async fn test_completion_reasons() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let operation = RawOperation {
+        query: "subscription { books { id } }",
+    };
+    let completed = client.subscribe(operation.clone()).await.unwrap();
+    let errored = client.subscribe(operation.clone()).await.unwrap();
+    let stopped = client.subscribe(operation.clone()).await.unwrap();
+    let running = client.subscribe(operation).await.unwrap();
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+    for _ in 0..4 {
+        assert_eq!(server.receive().await["type"], "subscribe");
+    }
+
+    server.send(json!({"type": "complete", "id": completed.id().as_str()}));
+    assert_eq!(completed.completion().await, CompletionReason::Completed);
+
+    server.send(json!({"type": "error", "id": errored.id().as_str(), "payload": []}));
+    assert_eq!(errored.completion().await, CompletionReason::Errored);
+
+    let completion = stopped.completion();
+    stopped.stop().await;
+    assert_eq!(completion.await, CompletionReason::Stopped);
+
+    server.close(CloseCode::InternalServerError, "oops");
+    let reason = running.completion().await;
+    assert_eq!(
+        reason,
+        CompletionReason::ServerClosed {
+            code: Some(CloseCode::InternalServerError),
+            reason: Some("oops".into())
+        }
+    );
+    assert!(reason.is_transport_failure());
+}
+
+#[tokio::test]
+async fn test_fake_server_helpers() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection)
+        .payload(json!({"token": "abc"}))
+        .unwrap()
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    assert_eq!(server.receive_init().await, Some(json!({"token": "abc"})));
+
+    let mut books = client
+        .subscribe(RawOperation {
+            query: "subscription { books { id } }",
+        })
+        .await
+        .unwrap();
+    let mut authors = client
+        .subscribe(RawOperation {
+            query: "subscription { authors { id } }",
+        })
+        .await
+        .unwrap();
+
+    let books_id = server
+        .expect_subscribe(json!({"query": "subscription { books { id } }"}))
+        .await;
+    let subscribe = server.receive_subscribe().await;
+    assert_eq!(subscribe.id, authors.id().as_str());
+    assert_eq!(
+        subscribe.payload["query"],
+        "subscription { authors { id } }"
+    );
+
+    server.next(&books_id, json!({"data": {"books": {"id": 1}}}));
+    server.complete(&books_id);
+
+    assert_eq!(
+        books.next().await.unwrap().unwrap()["data"]["books"]["id"],
+        1
+    );
+    assert!(books.next().await.is_none());
+
+    server.error(authors.id().as_str(), json!([{"message": "no authors"}]));
+    assert_matches!(
+        authors.next().await,
+        Some(Err(Error::Operation(errors))) if errors[0].message == "no authors"
+    );
+}
+
+#[tokio::test]
+async fn test_stop_waits_for_complete() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let operation = RawOperation {
+        query: "subscription { books { id } }",
+    };
+    let first = client.subscribe(operation.clone()).await.unwrap();
+    let second = client.subscribe(operation).await.unwrap();
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+    assert_eq!(server.receive().await["type"], "subscribe");
+    assert_eq!(server.receive().await["type"], "subscribe");
+
+    let id = first.id();
+    first.stop().await;
+    assert_eq!(
+        server.try_receive(),
+        Some(json!({"type": "complete", "id": id.as_str()}))
+    );
+
+    client.stop(second.id()).await.unwrap();
+    assert_eq!(
+        server.try_receive(),
+        Some(json!({"type": "complete", "id": second.id().as_str()}))
+    );
+}
+
+#[tokio::test]
+async fn test_shutdown_drains_subscriptions() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let operation = RawOperation {
+        query: "subscription { books { id } }",
+    };
+    let mut stream = client.subscribe(operation.clone()).await.unwrap();
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+    let id = server.receive().await["id"].clone();
+
+    server.send(json!({"type": "next", "id": id, "payload": {"data": 1}}));
+    server.send(json!({"type": "ping"}));
+    assert_eq!(server.receive().await["type"], "pong");
+
+    let shutdown = tokio::spawn(client.clone().shutdown(Duration::from_secs(5)));
+
+    assert_eq!(
+        server.receive().await,
+        json!({"type": "complete", "id": id})
+    );
+
+    // New subscriptions should be rejected while shutting down
+    let error = client.subscribe(operation).await.err().unwrap();
+    assert_matches!(error, Error::ShuttingDown);
+
+    // The connection should still be serviced until the stream has been read
+    server.send(json!({"type": "ping"}));
+    assert_eq!(server.receive().await["type"], "pong");
+
+    // Responses that were already received should still be delivered
+    assert_eq!(stream.next().await.unwrap().unwrap()["data"], 1);
+    assert_matches!(stream.next().await, None);
+
+    assert_eq!(server.receive_close().await, Some(CloseCode::Normal));
+    shutdown.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_shutdown_timeout() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let operation = RawOperation {
+        query: "subscription { books { id } }",
+    };
+    let mut first = client.subscribe(operation.clone()).await.unwrap();
+    let second = client.subscribe(operation).await.unwrap();
+    let first_id = server.receive_subscribe().await.id;
+    server.receive_subscribe().await;
+
+    server.next(&first_id, json!({"data": 1}));
+    server.send(json!({"type": "ping"}));
+    assert_eq!(server.receive().await["type"], "pong");
+
+    // Neither stream is read, so the connection should close after the timeout
+    client.shutdown(Duration::from_millis(50)).await;
+    assert_eq!(second.completion().await, CompletionReason::Stopped);
+
+    // The buffered response should still be delivered after the close
+    assert_eq!(first.next().await.unwrap().unwrap()["data"], 1);
+    assert_matches!(first.next().await, None);
+
+    assert_eq!(server.receive().await["type"], "complete");
+    assert_eq!(server.receive().await["type"], "complete");
+    assert_eq!(server.receive_close().await, Some(CloseCode::Normal));
+}
+
+#[tokio::test]
+async fn test_close_during_shutdown() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    let actor = tokio::spawn(actor.into_future());
+
+    let _stream = client
+        .subscribe(RawOperation {
+            query: "subscription { books { id } }",
+        })
+        .await
+        .unwrap();
+    let id = server.receive_subscribe().await.id;
+
+    let first = tokio::spawn(client.clone().shutdown(Duration::from_secs(60)));
+    assert_eq!(
+        server.receive().await,
+        json!({"type": "complete", "id": id})
+    );
+
+    // A second shutdown should wait for the first to close the connection
+    let second = tokio::spawn(client.clone().shutdown(Duration::from_secs(60)));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!second.is_finished());
+
+    // Closing shouldn't wait for the stream to be drained
+    client.close(CloseCode::Other(4000), "go away").await;
+
+    assert_eq!(server.receive_close().await, Some(CloseCode::Other(4000)));
+    assert_eq!(
+        actor.await.unwrap(),
+        ShutdownReason::ClientClosed {
+            code: CloseCode::Other(4000),
+            reason: Some("go away".into())
+        }
+    );
+    first.await.unwrap();
+    second.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_shutdown_reason() {
+    let (connection, server) = connection_pair();
+
+    server.ack();
+
+    let (_client, actor) = Client::build(connection).await.unwrap();
+
+    let handle = actor.handle();
+    let actor = tokio::spawn(actor.into_future());
+
+    server.close(CloseCode::InternalServerError, "go away");
+
+    let expected = ShutdownReason::ServerClosed {
+        code: Some(CloseCode::InternalServerError),
+        reason: Some("go away".into()),
+    };
+    assert_eq!(actor.await.unwrap(), expected);
+    assert_eq!(handle.shutdown_reason().await, expected);
+    assert!(expected.is_transport_failure());
+}
+
+#[tokio::test]
+async fn test_shutdown_reason_after_client_close() {
+    let (connection, server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    let handle = actor.handle();
+    tokio::spawn(actor.into_future());
+
+    client.close(CloseCode::Normal, "done").await;
+
+    assert_eq!(
+        handle.shutdown_reason().await,
+        ShutdownReason::ClientClosed {
+            code: CloseCode::Normal,
+            reason: Some("done".into())
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_ignoring_malformed_messages() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let malformed = Arc::new(Mutex::new(Vec::new()));
+    let (client, actor) = Client::build(connection)
+        .malformed_message_policy(MalformedMessagePolicy::Ignore)
+        .on_malformed_message({
+            let malformed = Arc::clone(&malformed);
+            move |message, _| malformed.lock().unwrap().push(message.to_string())
+        })
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let mut stream = client
+        .subscribe(RawOperation {
+            query: "subscription { books { id } }",
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+    let id = server.receive().await["id"].clone();
+
+    server.send(json!({"type": "surprise", "id": id}));
+    server.send(json!({"type": "next", "id": id, "payload": {"data": 1}}));
+
+    assert_eq!(stream.next().await.unwrap().unwrap()["data"], 1);
+    assert_eq!(
+        *malformed.lock().unwrap(),
+        vec![json!({"type": "surprise", "id": id}).to_string()]
+    );
+}
+
+#[tokio::test]
+async fn test_incremental_delivery() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let mut stream = client
+        .subscribe(RawOperation {
+            query: "query { book { id ... @defer { author } } books @stream { id } }",
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+    let id = server.receive().await["id"].clone();
+
+    let payloads = [
+        json!({
+            "data": {"book": {"id": "1"}, "books": [{"id": "a"}]},
+            "hasNext": true
+        }),
+        json!({
+            "incremental": [{"data": {"author": "Le Guin"}, "path": ["book"]}],
+            "hasNext": true
+        }),
+        json!({
+            "incremental": [{
+                "items": [{"id": "b"}, {"id": "c"}],
+                "path": ["books", 1],
+                "errors": [{"message": "slow book"}]
+            }],
+            "hasNext": false
+        }),
+    ];
+    for payload in payloads {
+        server.send(json!({"type": "next", "id": id, "payload": payload}));
+    }
+    server.send(json!({"type": "complete", "id": id}));
+
+    assert_eq!(
+        stream.next().await.unwrap().unwrap(),
+        json!({
+            "data": {
+                "book": {"id": "1", "author": "Le Guin"},
+                "books": [{"id": "a"}, {"id": "b"}, {"id": "c"}]
+            },
+            "errors": [{"message": "slow book"}]
+        })
+    );
+    assert_matches!(stream.next().await, None);
+}
+
+#[tokio::test]
+async fn test_incremental_delivery_with_pending_ids() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection).await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let mut stream = client
+        .subscribe(RawOperation {
+            query: "query { book { id ... @defer { author { name } } } books @stream { id } }",
+        })
+        .await
+        .unwrap();
+
+    let id = server.receive_subscribe().await.id;
+
+    let payloads = [
+        json!({
+            "data": {"book": {"id": "1", "author": {}}, "books": [{"id": "a"}]},
+            "pending": [
+                {"id": "0", "path": ["book"]},
+                {"id": "1", "path": ["books"]}
+            ],
+            "hasNext": true
+        }),
+        json!({
+            "incremental": [
+                {"id": "0", "data": {"name": "Le Guin"}, "subPath": ["author"]},
+                {"id": "1", "items": [{"id": "b"}]}
+            ],
+            "completed": [{"id": "0"}],
+            "hasNext": true
+        }),
+        json!({
+            "incremental": [{"id": "1", "items": [{"id": "c"}]}],
+            "completed": [{"id": "1", "errors": [{"message": "slow book"}]}],
+            "hasNext": false
+        }),
+    ];
+    for payload in payloads {
+        server.next(&id, payload);
+    }
+    server.complete(&id);
+
+    assert_eq!(
+        stream.next().await.unwrap().unwrap(),
+        json!({
+            "data": {
+                "book": {"id": "1", "author": {"name": "Le Guin"}},
+                "books": [{"id": "a"}, {"id": "b"}, {"id": "c"}]
+            },
+            "errors": [{"message": "slow book"}]
+        })
+    );
+    assert_matches!(stream.next().await, None);
+}
+
+#[tokio::test]
+async fn test_automatic_persisted_queries() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection)
+        .persisted_queries(PersistedQueries::automatic())
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let mut stream = client
+        .subscribe(RawOperation {
+            query: "subscription { books { id } }",
+        })
+        .await
+        .unwrap();
+
+    let persisted_query = json!({
+        "version": 1,
+        "sha256Hash": "acea30eecba693b6ebc71e30ffb4e9ddc6dc3c0221959dfa1203da6e8f349f75"
+    });
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+    let message = server.receive().await;
+    let id = message["id"].clone();
+    assert_eq!(
+        message["payload"],
+        json!({"extensions": {"persistedQuery": persisted_query}})
+    );
+
+    server.send(json!({
+        "type": "error",
+        "id": id,
+        "payload": [{"message": "PersistedQueryNotFound"}]
+    }));
+
+    // The operation should be retried with the full query
+    let message = server.receive().await;
+    assert_eq!(message["id"], id);
+    assert_eq!(
+        message["payload"],
+        json!({
+            "query": "subscription { books { id } }",
+            "extensions": {"persistedQuery": persisted_query}
+        })
+    );
+
+    server.send(json!({"type": "next", "id": id, "payload": {"data": 1}}));
+    assert_eq!(stream.next().await.unwrap().unwrap()["data"], 1);
+}
+
+/// An operation that is sent with its own `extensions`
+#[derive(serde::Serialize)]
+struct OperationWithExtensions {
+    query: &'static str,
+    extensions: Value,
+}
+
+impl GraphqlOperation for OperationWithExtensions {
+    type Response = Value;
+
+    type Error = serde_json::Error;
+
+    fn decode(&self, data: Value) -> Result<Self::Response, Self::Error> {
+        Ok(data)
+    }
+}
+
+#[tokio::test]
+async fn test_automatic_persisted_queries_keep_extensions() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection)
+        .persisted_queries(PersistedQueries::automatic())
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let _object = client
+        .subscribe(OperationWithExtensions {
+            query: "subscription { books { id } }",
+            extensions: json!({"tracing": true}),
+        })
+        .await
+        .unwrap();
+    let subscribe = server.receive_subscribe().await;
+    assert_eq!(subscribe.payload.get("query"), None);
+    assert_eq!(subscribe.payload["extensions"]["tracing"], true);
+    assert_eq!(
+        subscribe.payload["extensions"]["persistedQuery"]["version"],
+        1
+    );
+
+    // Extensions that aren't an object are left alone, and the query is sent in full
+    let _string = client
+        .subscribe(OperationWithExtensions {
+            query: "subscription { books { id } }",
+            extensions: json!("unexpected"),
+        })
+        .await
+        .unwrap();
+    let subscribe = server.receive_subscribe().await;
+    assert_eq!(
+        subscribe.payload,
+        json!({
+            "query": "subscription { books { id } }",
+            "extensions": "unexpected"
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_automatic_persisted_queries_not_found_in_next() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection)
+        .persisted_queries(PersistedQueries::automatic())
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let mut stream = client
+        .subscribe(RawOperation {
+            query: "subscription { books { id } }",
+        })
+        .await
+        .unwrap();
+
+    let subscribe = server.receive_subscribe().await;
+    assert_eq!(subscribe.payload.get("query"), None);
+
+    server.next(
+        &subscribe.id,
+        json!({
+            "errors": [{
+                "message": "PersistedQueryNotFound",
+                "extensions": {"code": "PERSISTED_QUERY_NOT_FOUND"}
+            }]
+        }),
+    );
+    server.complete(&subscribe.id);
+
+    // The operation should be retried with the full query once the server has
+    // completed the first attempt
+    let retry = server.receive_subscribe().await;
+    assert_eq!(retry.id, subscribe.id);
+    assert_eq!(retry.payload["query"], "subscription { books { id } }");
+
+    server.next(&retry.id, json!({"data": 1}));
+    assert_eq!(stream.next().await.unwrap().unwrap()["data"], 1);
+}
+
+#[tokio::test]
+async fn test_persisted_document_ids() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection)
+        .persisted_queries(PersistedQueries::document_ids(|query| {
+            (query == "subscription { books { id } }").then(|| "books-v1".to_string())
+        }))
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let _stream = client
+        .subscribe(RawOperation {
+            query: "subscription { books { id } }",
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+    assert_eq!(
+        server.receive().await["payload"],
+        json!({"documentId": "books-v1"})
+    );
+}
+
+#[tokio::test]
+async fn test_latency() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection)
+        .keep_alive_interval(Duration::from_millis(20))
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+    assert_eq!(client.latency(), None);
+
+    let mut events = client.events();
+    for _ in 0..2 {
+        assert_eq!(server.receive().await["type"], "ping");
+        server.send(json!({"type": "pong"}));
+
+        while !matches!(events.next().await, Some(ConnectionEvent::Pong { .. })) {}
+    }
+
+    let latency = client.latency().unwrap();
+    assert_eq!(latency.samples, 2);
+    assert!(latency.min <= latency.last && latency.last <= latency.max);
+    assert!(latency.min <= latency.average && latency.average <= latency.max);
+}
+
+#[tokio::test]
+async fn test_latency_is_timed_from_the_latest_ping() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection)
+        .keep_alive_interval(Duration::from_millis(100))
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+
+    // A pong that isn't answering one of our pings shouldn't be timed
+    let mut events = client.events();
+    assert_matches!(events.next().await, Some(ConnectionEvent::Connected { .. }));
+    server.send(json!({"type": "pong"}));
+    assert_matches!(
+        events.next().await,
+        Some(ConnectionEvent::Pong {
+            round_trip_time: None,
+            ..
+        })
+    );
+    assert_eq!(client.latency(), None);
+
+    // Only answer the second ping, which should be timed from when it was sent
+    assert_eq!(server.receive().await["type"], "ping");
+    assert_eq!(server.receive().await["type"], "ping");
+    server.send(json!({"type": "pong"}));
+
+    let round_trip_time = loop {
+        if let Some(ConnectionEvent::Pong {
+            round_trip_time, ..
+        }) = events.next().await
+        {
+            break round_trip_time.unwrap();
+        }
+    };
+    assert!(round_trip_time < Duration::from_millis(100));
+    assert_eq!(client.latency().unwrap().samples, 1);
+}
+
+#[tokio::test]
+async fn test_connection_init_timeout() {
+    let (connection, mut server) = connection_pair();
+
+    let result = Client::build(connection)
+        .connection_init_timeout(Duration::from_millis(10))
+        .await;
+
+    assert_matches!(result.err(), Some(Error::ConnectionInitTimeout));
+    assert_eq!(
+        server.receive_close().await,
+        Some(CloseCode::ConnectionInitialisationTimeout)
+    );
+}
+
+#[tokio::test]
+async fn test_server_close_without_code_is_echoed_as_normal() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (_client, actor) = Client::build(connection).await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    server.close(CloseCode::NoStatusReceived, "");
+    assert_eq!(server.receive_close().await, Some(CloseCode::Normal));
+}
+
+#[test]
+fn test_close_codes_compare_by_number() {
+    assert_eq!(CloseCode::Other(1000), CloseCode::Normal);
+    assert_eq!(CloseCode::from(4403), CloseCode::Forbidden);
+    assert_ne!(CloseCode::Other(4000), CloseCode::Normal);
+
+    let codes = std::collections::HashSet::from([CloseCode::Other(4403), CloseCode::Forbidden]);
+    assert_eq!(codes.len(), 1);
+}
+
+#[test]
+fn test_path_segments_deserialize() {
+    let path: Vec<PathSegment> = serde_json::from_value(json!(["books", 0, "id"])).unwrap();
+
+    assert_eq!(
+        path,
+        vec![
+            PathSegment::Field("books".into()),
+            PathSegment::Index(0),
+            PathSegment::Field("id".into())
+        ]
+    );
+}
@@ -0,0 +1,85 @@
+use std::future::IntoFuture;
+
+use assert_matches::assert_matches;
+use graphql_ws_client::{ClientBuilder, Error, Protocol, tokio_tungstenite_0_27::ClientBuilderExt};
+use raw_operation::RawOperation;
+use serde_json::json;
+use subscription_server::SubscriptionServer;
+use tungstenite_0_27::{client::IntoClientRequest, http::HeaderValue};
+
+mod raw_operation;
+
+// These tests don't send subscription updates
+#[allow(dead_code)]
+mod subscription_server;
+
+const QUERY: RawOperation = RawOperation {
+    query: "query { books { name } }",
+};
+
+#[tokio::test]
+async fn test_connect() {
+    let server = SubscriptionServer::start().await;
+
+    let (client, actor) = ClientBuilder::connect(&server.websocket_url())
+        .await
+        .unwrap()
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let response = client.execute(QUERY).await.unwrap();
+
+    assert_eq!(
+        response["data"]["books"],
+        json!([{"name": "Cynic for Beginners"}])
+    );
+}
+
+#[tokio::test]
+async fn test_connect_with_legacy_subprotocol() {
+    let server = SubscriptionServer::start().await;
+
+    let mut request = server.websocket_url().into_client_request().unwrap();
+    request.headers_mut().insert(
+        "Sec-WebSocket-Protocol",
+        HeaderValue::from_static(Protocol::SubscriptionsTransportWs.subprotocol()),
+    );
+
+    let (client, actor) = ClientBuilder::connect_with_request(request)
+        .await
+        .unwrap()
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let response = client.execute(QUERY).await.unwrap();
+
+    assert_eq!(
+        response["data"]["books"],
+        json!([{"name": "Cynic for Beginners"}])
+    );
+}
+
+#[tokio::test]
+async fn test_connect_subprotocol_not_selected() {
+    // A websocket server that doesn't select any subprotocol
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        tokio_tungstenite_0_27::accept_async(stream).await.ok();
+    });
+
+    let error = ClientBuilder::connect(&format!("ws://localhost:{port}/ws"))
+        .await
+        .err()
+        .unwrap();
+
+    assert_matches!(
+        error,
+        Error::SubprotocolNotSelected(requested) if requested == "graphql-transport-ws"
+    );
+}
@@ -164,7 +164,7 @@ async fn test_subscription_stop() {
 
     assert_eq!(server.subscriber_count(), 1);
 
-    stream.stop();
+    stream.stop().await;
 
     sleep(Duration::from_millis(10)).await;
 
@@ -200,6 +200,31 @@ async fn test_subscription_stops_on_drop() {
     assert_eq!(server.subscriber_count(), 0);
 }
 
+#[derive(cynic::QueryFragment, Debug)]
+#[cynic(schema_path = "schemas/books.graphql", graphql_type = "QueryRoot")]
+struct BooksQuery {
+    books: Vec<Book>,
+}
+
+#[tokio::test]
+async fn test_query() {
+    use cynic::QueryBuilder;
+
+    let server = SubscriptionServer::start().await;
+
+    let client_builder = server.client_builder().await;
+    let (client, actor) = client_builder.await.unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let response = client.execute(BooksQuery::build(())).await.unwrap();
+
+    assert_matches!(response.errors, None);
+    let books = response.data.unwrap().books;
+    assert_eq!(books.len(), 1);
+    assert_eq!(books[0].name, "Cynic for Beginners");
+}
+
 async fn send_and_verify_updates(
     server: &SubscriptionServer,
     updates: &[subscription_server::BookChanged],
@@ -0,0 +1,163 @@
+use std::{
+    future::IntoFuture,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use assert_matches::assert_matches;
+use futures_lite::StreamExt;
+use graphql_ws_client::{
+    Client, CloseCode, CompletionReason, Error, MalformedMessagePolicy, Protocol, ShutdownReason,
+    testing::connection_pair,
+};
+use raw_operation::RawOperation;
+use serde_json::json;
+
+mod raw_operation;
+
+#[tokio::test]
+async fn test_legacy_protocol_subscription() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+    server.send(json!({"type": "ka"}));
+
+    let (client, actor) = Client::build(connection)
+        .protocol(Protocol::SubscriptionsTransportWs)
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let mut stream = client
+        .subscribe(RawOperation {
+            query: "subscription { books { id } }",
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+    let start = server.receive().await;
+    assert_eq!(start["type"], "start");
+    assert_eq!(start["payload"]["query"], "subscription { books { id } }");
+
+    let id = start["id"].clone();
+
+    server.send(json!({"type": "ka"}));
+    server.send(json!({
+        "type": "data",
+        "id": id,
+        "payload": {"data": {"books": {"id": "123"}}}
+    }));
+    server.send(json!({
+        "type": "error",
+        "id": id,
+        "payload": {"message": "something went wrong"}
+    }));
+
+    let response = stream.next().await.unwrap().unwrap();
+    assert_eq!(response["data"]["books"]["id"], "123");
+
+    let error = stream.next().await.unwrap().unwrap_err();
+    assert_matches!(error, Error::Operation(errors) if errors[0].message == "something went wrong");
+
+    assert!(stream.next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_legacy_protocol_stop() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection)
+        .protocol(Protocol::SubscriptionsTransportWs)
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let stream = client
+        .subscribe(RawOperation {
+            query: "subscription { books { id } }",
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+    let id = server.receive().await["id"].clone();
+
+    client.stop(stream.id()).await.unwrap();
+
+    assert_eq!(server.receive().await, json!({"type": "stop", "id": id}));
+}
+
+#[tokio::test]
+async fn test_legacy_protocol_connection_error() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection)
+        .protocol(Protocol::SubscriptionsTransportWs)
+        .await
+        .unwrap();
+
+    let actor = tokio::spawn(actor.into_future());
+
+    let stream = client
+        .subscribe(RawOperation {
+            query: "subscription { books { id } }",
+        })
+        .await
+        .unwrap();
+    let completion = stream.completion();
+
+    assert_eq!(server.receive().await["type"], "connection_init");
+    assert_eq!(server.receive().await["type"], "start");
+
+    server.send(json!({
+        "type": "connection_error",
+        "payload": {"message": "session expired"}
+    }));
+
+    assert_eq!(server.receive_close().await, Some(CloseCode::Normal));
+    assert_eq!(
+        actor.await.unwrap(),
+        ShutdownReason::ServerRejected {
+            payload: Some(json!({"message": "session expired"}))
+        }
+    );
+    assert_eq!(completion.await, CompletionReason::ServerRejected);
+}
+
+#[tokio::test]
+async fn test_legacy_protocol_connection_error_is_not_ignored() {
+    let (connection, mut server) = connection_pair();
+
+    server.ack();
+
+    let hook_called = Arc::new(AtomicBool::new(false));
+    let (_client, actor) = Client::build(connection)
+        .protocol(Protocol::SubscriptionsTransportWs)
+        .malformed_message_policy(MalformedMessagePolicy::Ignore)
+        .on_malformed_message({
+            let hook_called = Arc::clone(&hook_called);
+            move |_, _| hook_called.store(true, Ordering::SeqCst)
+        })
+        .await
+        .unwrap();
+
+    let actor = tokio::spawn(actor.into_future());
+
+    server.send(json!({"type": "connection_error"}));
+
+    assert_eq!(server.receive_close().await, Some(CloseCode::Normal));
+    assert_eq!(
+        actor.await.unwrap(),
+        ShutdownReason::ServerRejected { payload: None }
+    );
+    assert!(!hook_called.load(Ordering::SeqCst));
+}
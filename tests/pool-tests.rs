@@ -0,0 +1,169 @@
+use std::future::IntoFuture;
+
+use assert_matches::assert_matches;
+use futures_lite::StreamExt;
+use graphql_ws_client::{
+    ActorHandle, Client, ClientPool, CompletionReason, Error,
+    testing::{FakeServer, connection_pair},
+};
+use raw_operation::RawOperation;
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+
+mod raw_operation;
+
+const QUERY: RawOperation = RawOperation {
+    query: "subscription { books { id } }",
+};
+
+#[tokio::test]
+async fn test_subscriptions_are_spread_across_connections() {
+    let (pool, mut servers, _handles) = build_pool(2, None);
+
+    let first = pool.subscribe(QUERY).await.unwrap();
+    let second = pool.subscribe(QUERY).await.unwrap();
+
+    let mut first_server = servers.recv().await.unwrap();
+    let mut second_server = servers.recv().await.unwrap();
+    assert_eq!(pool.connection_count(), 2);
+
+    assert_eq!(
+        first_server.receive_subscribe().await.id,
+        first.id().as_str()
+    );
+    assert_eq!(
+        second_server.receive_subscribe().await.id,
+        second.id().as_str()
+    );
+
+    // Stopping a subscription should make its connection the least loaded
+    first.stop().await;
+    assert_eq!(first_server.receive().await["type"], "complete");
+
+    let _third = pool.subscribe(QUERY).await.unwrap();
+    first_server.receive_subscribe().await;
+
+    // The pool is at its connection limit, so no more connections should be opened
+    let _fourth = pool.subscribe(QUERY).await.unwrap();
+    assert_eq!(pool.connection_count(), 2);
+    assert!(servers.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_max_operations_per_connection() {
+    let (pool, mut servers, _handles) = build_pool(1, Some(1));
+
+    let mut stream = pool.subscribe(QUERY).await.unwrap();
+    let mut server = servers.recv().await.unwrap();
+
+    assert_matches!(
+        pool.subscribe(QUERY).await.err(),
+        Some(Error::PoolExhausted)
+    );
+
+    let subscribe = server.receive_subscribe().await;
+    server.complete(&subscribe.id);
+    assert!(stream.next().await.is_none());
+
+    pool.subscribe(QUERY).await.unwrap();
+    server.receive_subscribe().await;
+    assert!(servers.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_closed_connections_are_replaced() {
+    let (pool, mut servers, mut handles) = build_pool(1, None);
+
+    let stream = pool.subscribe(QUERY).await.unwrap();
+    let server = servers.recv().await.unwrap();
+
+    drop(server);
+    handles.recv().await.unwrap().shutdown_reason().await;
+    assert_eq!(stream.completion().await, CompletionReason::ConnectionLost);
+    assert_eq!(pool.connection_count(), 0);
+
+    let stream = pool.subscribe(QUERY).await.unwrap();
+    let mut server = servers.recv().await.unwrap();
+    assert_eq!(server.receive_subscribe().await.id, stream.id().as_str());
+}
+
+#[tokio::test]
+async fn test_concurrent_subscribes_wait_for_opening_connection() {
+    let (new_servers, mut servers) = unbounded_channel();
+
+    // The servers aren't acked until the test does so, so both subscribes
+    // start while the first connection is still opening
+    let pool = ClientPool::builder(
+        move || {
+            let (connection, server) = connection_pair();
+            new_servers.send(server).unwrap();
+            async move { Ok(Client::build(connection)) }
+        },
+        |actor| {
+            tokio::spawn(actor.into_future());
+        },
+    )
+    .max_connections(1)
+    .build();
+
+    let first = tokio::spawn({
+        let pool = pool.clone();
+        async move { pool.subscribe(QUERY).await }
+    });
+    let second = tokio::spawn({
+        let pool = pool.clone();
+        async move { pool.subscribe(QUERY).await }
+    });
+
+    let mut server = servers.recv().await.unwrap();
+    server.ack();
+
+    let first = first.await.unwrap().unwrap();
+    let second = second.await.unwrap().unwrap();
+
+    let mut ids = vec![
+        server.receive_subscribe().await.id,
+        server.receive_subscribe().await.id,
+    ];
+    ids.sort();
+    let mut expected = vec![first.id().to_string(), second.id().to_string()];
+    expected.sort();
+    assert_eq!(ids, expected);
+
+    assert_eq!(pool.connection_count(), 1);
+    assert!(servers.try_recv().is_err());
+}
+
+/// Builds a pool whose connections are made with `connection_pair`, returning
+/// receivers for the server side of each connection & the actor handles
+fn build_pool(
+    max_connections: usize,
+    max_operations: Option<usize>,
+) -> (
+    ClientPool,
+    UnboundedReceiver<FakeServer>,
+    UnboundedReceiver<ActorHandle>,
+) {
+    let (new_servers, servers) = unbounded_channel();
+    let (new_handles, handles) = unbounded_channel();
+
+    let builder = ClientPool::builder(
+        move || {
+            let (connection, server) = connection_pair();
+            server.ack();
+            new_servers.send(server).unwrap();
+            async move { Ok(Client::build(connection)) }
+        },
+        move |actor| {
+            new_handles.send(actor.handle()).unwrap();
+            tokio::spawn(actor.into_future());
+        },
+    )
+    .max_connections(max_connections);
+
+    let builder = match max_operations {
+        Some(max_operations) => builder.max_operations_per_connection(max_operations),
+        None => builder,
+    };
+
+    (builder.build(), servers, handles)
+}
@@ -0,0 +1,18 @@
+use graphql_ws_client::graphql::GraphqlOperation;
+use serde_json::Value;
+
+/// An operation that passes the raw response through without decoding it
+#[derive(Clone, serde::Serialize)]
+pub struct RawOperation {
+    pub query: &'static str,
+}
+
+impl GraphqlOperation for RawOperation {
+    type Response = Value;
+
+    type Error = serde_json::Error;
+
+    fn decode(&self, data: Value) -> Result<Self::Response, Self::Error> {
+        Ok(data)
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    future::IntoFuture,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use futures_lite::StreamExt;
+use graphql_ws_client::{Client, CloseCode, ShutdownReason, testing::connection_pair};
+use raw_operation::RawOperation;
+use serde_json::json;
+use tokio::sync::mpsc::unbounded_channel;
+
+mod raw_operation;
+
+#[tokio::test]
+async fn test_subscriptions_are_replayed_after_reconnect() {
+    let (connection, mut server) = connection_pair();
+    let (new_servers, mut new_servers_receiver) = unbounded_channel();
+
+    server.ack();
+
+    let tokens_issued = Arc::new(AtomicUsize::new(0));
+
+    let (client, actor) = Client::build(connection)
+        .payload_provider(move || {
+            let token = tokens_issued.fetch_add(1, Ordering::Relaxed) + 1;
+            async move { json!({ "token": token }) }
+        })
+        .reconnect(move || {
+            let (connection, server) = connection_pair();
+            server.ack();
+            new_servers.send(server).unwrap();
+            async move { Ok(connection) }
+        })
+        .reconnect_delay(Duration::from_millis(1))
+        .await
+        .unwrap();
+
+    tokio::spawn(actor.into_future());
+
+    let mut stream = client
+        .subscribe(RawOperation {
+            query: "subscription { books { id } }",
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(
+        server.receive().await,
+        json!({"type": "connection_init", "payload": {"token": 1}})
+    );
+    let subscribe = server.receive().await;
+    assert_eq!(subscribe["type"], "subscribe");
+
+    // Drop the connection, and make sure the client re-establishes it
+    drop(server);
+    let mut server = new_servers_receiver.recv().await.unwrap();
+
+    // Each connection should get a freshly computed payload
+    assert_eq!(
+        server.receive().await,
+        json!({"type": "connection_init", "payload": {"token": 2}})
+    );
+    assert_eq!(server.receive().await, subscribe);
+
+    server.send(json!({
+        "type": "next",
+        "id": subscribe["id"],
+        "payload": {"data": {"books": {"id": "123"}}}
+    }));
+
+    let response = stream.next().await.unwrap().unwrap();
+    assert_eq!(response["data"]["books"]["id"], "123");
+}
+
+#[tokio::test]
+async fn test_rejections_are_not_reconnected() {
+    let (connection, server) = connection_pair();
+    let reconnects = Arc::new(AtomicUsize::new(0));
+
+    server.ack();
+
+    let (client, actor) = Client::build(connection)
+        .reconnect({
+            let reconnects = Arc::clone(&reconnects);
+            move || {
+                reconnects.fetch_add(1, Ordering::Relaxed);
+                let (connection, server) = connection_pair();
+                server.ack();
+                async move { Ok(connection) }
+            }
+        })
+        .reconnect_delay(Duration::from_millis(1))
+        .await
+        .unwrap();
+
+    let actor = tokio::spawn(actor.into_future());
+
+    let stream = client
+        .subscribe(RawOperation {
+            query: "subscription { books { id } }",
+        })
+        .await
+        .unwrap();
+
+    server.close(CloseCode::Forbidden, "Forbidden");
+
+    let reason = actor.await.unwrap();
+    assert_eq!(
+        reason,
+        ShutdownReason::ServerClosed {
+            code: Some(CloseCode::Forbidden),
+            reason: Some("Forbidden".into())
+        }
+    );
+    assert!(!reason.is_transport_failure());
+    assert!(!stream.completion().await.is_transport_failure());
+    assert_eq!(reconnects.load(Ordering::Relaxed), 0);
+}
+
+#[tokio::test]
+async fn test_transient_44xx_closes_are_reconnected() {
+    for code in [
+        CloseCode::ConnectionInitialisationTimeout,
+        CloseCode::Terminated,
+    ] {
+        let (connection, server) = connection_pair();
+        let (new_servers, mut new_servers_receiver) = unbounded_channel();
+
+        server.ack();
+
+        let (client, actor) = Client::build(connection)
+            .reconnect(move || {
+                let (connection, server) = connection_pair();
+                server.ack();
+                new_servers.send(server).unwrap();
+                async move { Ok(connection) }
+            })
+            .reconnect_delay(Duration::from_millis(1))
+            .await
+            .unwrap();
+
+        tokio::spawn(actor.into_future());
+
+        let _stream = client
+            .subscribe(RawOperation {
+                query: "subscription { books { id } }",
+            })
+            .await
+            .unwrap();
+
+        server.close(code, "try again");
+
+        let mut server = new_servers_receiver.recv().await.unwrap();
+        server.receive_subscribe().await;
+    }
+}
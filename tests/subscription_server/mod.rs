@@ -132,6 +132,14 @@ impl QueryRoot {
     pub async fn id(&self) -> ID {
         "123".into()
     }
+
+    pub async fn books(&self) -> Vec<Book> {
+        vec![Book {
+            id: "123".into(),
+            name: "Cynic for Beginners".into(),
+            author: "Graeme".into(),
+        }]
+    }
 }
 
 #[derive(Clone, Debug, SimpleObject)]